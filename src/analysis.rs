@@ -0,0 +1,182 @@
+use crate::classify::MoveClass;
+use crate::countdown::Countdown;
+use crate::evaluation::{score_losses, Evaluator};
+use crate::grid::Grid;
+use crate::search::CancelToken;
+use crate::solver::solve;
+use crate::wall::Wall;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Analyses positions on a worker thread so the caller never waits for the advisor. Each position
+// is numbered and replaces the ones before it: positions that are replaced while queued are never
+// analysed and results for replaced positions are never published. With the solver on, every move
+// reaching the exact best score is published instead of the advisor's moves, and a solve still
+// running when its position is replaced is cancelled.
+//
+// Everything else the analysis window shows about a position is worked out here too, so drawing
+// never waits for it.
+// Time the advisor may spend ranking safe moves by long chain parity playouts.
+const RANKING_TIME: Duration = Duration::from_secs(1);
+
+pub struct Analyst {
+    requests: Sender<Request>,
+    latest: Arc<AtomicU64>,
+    // The number of threads to solve on, or None to use the advisor.
+    solver_threads: Option<usize>,
+    cancel: CancelToken,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub optimal_moves: HashSet<Wall>,
+    pub countdown: Countdown,
+    pub move_classes: HashMap<Wall, MoveClass>,
+    // Boxes lost against the best move, by the heuristic evaluator.
+    pub score_losses: HashMap<Wall, i32>,
+}
+
+struct Request {
+    number: u64,
+    game_grid: Grid,
+    solver_threads: Option<usize>,
+    cancel: CancelToken,
+}
+
+impl Analyst {
+    pub fn start<F>(mut publish: F) -> Self
+    where
+        F: FnMut(u64, Report) + Send + 'static,
+    {
+        let (requests, receiver) = mpsc::channel::<Request>();
+        let latest = Arc::new(AtomicU64::new(0));
+        let current = Arc::clone(&latest);
+        thread::spawn(move || {
+            while let Ok(mut request) = receiver.recv() {
+                // Skip to the newest position waiting.
+                while let Ok(newer) = receiver.try_recv() {
+                    request = newer;
+                }
+                let number = request.number;
+                if current.load(Ordering::SeqCst) != number {
+                    continue;
+                }
+                let optimal_moves = match request.solver_threads {
+                    Some(threads) => match solve(&request.game_grid, threads, &request.cancel) {
+                        Some(solution) => solution.best_moves.into_iter().collect(),
+                        None => continue,
+                    },
+                    None => request.game_grid.analyze_ranked(RANKING_TIME).moves,
+                };
+                let game_grid = &request.game_grid;
+                let report = Report {
+                    optimal_moves,
+                    countdown: game_grid.countdown(),
+                    move_classes: game_grid.classify_moves(),
+                    score_losses: score_losses(game_grid, Evaluator::Heuristic),
+                };
+                if current.load(Ordering::SeqCst) == number {
+                    publish(number, report);
+                }
+            }
+        });
+        Self {
+            requests,
+            latest,
+            solver_threads: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    pub fn set_solver_threads(&mut self, solver_threads: Option<usize>) {
+        // Takes effect from the next position analysed.
+        self.solver_threads = solver_threads;
+    }
+
+    pub fn solver_threads(&self) -> Option<usize> {
+        self.solver_threads
+    }
+
+    pub fn analyze(&mut self, game_grid: &Grid) -> u64 {
+        // Queue the position, replacing any earlier one, and return its number.
+        self.cancel.cancel();
+        self.cancel = CancelToken::new();
+        let number = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.requests.send(Request {
+            number,
+            game_grid: game_grid.clone(),
+            solver_threads: self.solver_threads,
+            cancel: self.cancel.clone(),
+        });
+        number
+    }
+
+    pub fn is_latest(&self, number: u64) -> bool {
+        self.latest.load(Ordering::SeqCst) == number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishes_the_latest_position() {
+        let (sender, receiver) = mpsc::channel();
+        let mut analyst = Analyst::start(move |number, report| {
+            let _ = sender.send((number, report));
+        });
+        let mut game_grid = Grid::new(3, 3);
+        analyst.analyze(&game_grid);
+        for notation in ["a1h", "a1v", "b1v"] {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        let last = analyst.analyze(&game_grid);
+
+        // Results for the first position may or may not arrive, but the last one always does.
+        let (number, report) = loop {
+            let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            if result.0 == last {
+                break result;
+            }
+        };
+        assert!(analyst.is_latest(number));
+        assert_eq!(
+            report,
+            Report {
+                optimal_moves: game_grid.analyze_ranked(RANKING_TIME).moves,
+                countdown: game_grid.countdown(),
+                move_classes: game_grid.classify_moves(),
+                score_losses: score_losses(&game_grid, Evaluator::Heuristic),
+            }
+        );
+    }
+
+    #[test]
+    fn publishes_solutions_with_the_solver_on() {
+        let (sender, receiver) = mpsc::channel();
+        let mut analyst = Analyst::start(move |number, report| {
+            let _ = sender.send((number, report));
+        });
+        analyst.set_solver_threads(Some(2));
+        // The empty grid is too large to solve, so it can only finish by being cancelled.
+        analyst.analyze(&Grid::new(6, 6));
+        let mut game_grid = Grid::new(3, 3);
+        for notation in ["a1h", "a1v", "b1v", "b2h"] {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        let last = analyst.analyze(&game_grid);
+        let (number, report) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(number, last);
+        let solution = solve(&game_grid, 1, &CancelToken::new()).unwrap();
+        assert_eq!(
+            report.optimal_moves,
+            solution.best_moves.into_iter().collect()
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use crate::classify::MoveClass;
+use crate::cursor::Cursor;
+use crate::grid::Grid;
+use crate::history::{MoveHistory, Owners};
+use crate::painter::Painter;
+use crate::theme::Theme;
+use crate::wall::{column_label, row_label, Wall};
+
+use flo_canvas::Color;
+use std::collections::{HashMap, HashSet};
+
+pub const SQUARE_LAYER: u64 = 0;
+pub const WALL_LAYER: u64 = 1;
+pub const DOT_LAYER: u64 = 2;
+pub const CURSOR_LAYER: u64 = 3;
+pub const ANNOTATION_LAYER: u64 = 4;
+pub const STATUS_LAYER: u64 = 5;
+
+pub const PLAYER_LETTERS: [&str; 2] = ["A", "B"];
+
+// Placement of the coordinate rulers and labels outside the grid (in grid units).
+pub const COORDINATE_MARGIN: f32 = 0.8;
+const RULER_OFFSET: f32 = -0.3;
+const RULER_TICK: f32 = 0.05;
+const RULER_WIDTH: f32 = 0.02;
+const LABEL_OFFSET: f32 = -0.55;
+const LABEL_HEIGHT: f32 = 0.3;
+// Swatches in the heatmap legend.
+const HEATMAP_LEGEND_STEPS: i32 = 5;
+
+pub fn draw_square<P: Painter>(
+    painter: &mut P,
+    column: f32,
+    row: f32,
+    wall_count: i32,
+    owner: Option<usize>,
+    theme: &Theme,
+) {
+    if let Some(owner) = owner {
+        // Completed squares with a known owner are filled with the player's color and letter.
+        painter.fill_rect(
+            SQUARE_LAYER,
+            (column, row),
+            (column + 1.0, row + 1.0),
+            theme.player_fill[owner],
+        );
+        painter.text(
+            SQUARE_LAYER,
+            (column + 0.5, row + 0.5),
+            0.4,
+            PLAYER_LETTERS[owner],
+            theme.dot_fill,
+        );
+        return;
+    }
+
+    painter.fill_rect(
+        SQUARE_LAYER,
+        (column, row),
+        (column + 1.0, row + 1.0),
+        theme.square_fill[wall_count as usize],
+    );
+
+    if theme.square_patterns && (wall_count == 2 || wall_count == 3) {
+        // Hatch squares with one diagonal for two walls and a cross for three walls.
+        painter.stroke_line(
+            SQUARE_LAYER,
+            (column + 0.2, row + 0.8),
+            (column + 0.8, row + 0.2),
+            0.05,
+            theme.dot_fill,
+        );
+        if wall_count == 3 {
+            painter.stroke_line(
+                SQUARE_LAYER,
+                (column + 0.2, row + 0.2),
+                (column + 0.8, row + 0.8),
+                0.05,
+                theme.dot_fill,
+            );
+        }
+    }
+}
+
+pub fn draw_dot<P: Painter>(painter: &mut P, column: f32, row: f32, theme: &Theme) {
+    painter.fill_circle(DOT_LAYER, (column, row), 0.1, theme.dot_fill);
+}
+
+pub fn draw_row<P: Painter>(painter: &mut P, column: f32, row: f32, color: Color, width: f32) {
+    painter.fill_rect(
+        WALL_LAYER,
+        (column, row - width / 2.0),
+        (column + 1.0, row + width / 2.0),
+        color,
+    );
+}
+
+pub fn draw_column<P: Painter>(painter: &mut P, column: f32, row: f32, color: Color, width: f32) {
+    painter.fill_rect(
+        WALL_LAYER,
+        (column - width / 2.0, row),
+        (column + width / 2.0, row + 1.0),
+        color,
+    );
+}
+
+pub fn draw_wall<P: Painter>(
+    painter: &mut P,
+    wall: &Wall,
+    optimal_moves: &HashSet<Wall>,
+    theme: &Theme,
+) {
+    let (color, width) = if optimal_moves.contains(wall) {
+        (theme.optimal_move_stroke, theme.optimal_move_width)
+    } else if wall.set {
+        (theme.line_stroke, theme.line_width)
+    } else {
+        return;
+    };
+    if wall.is_column {
+        draw_column(painter, wall.column as f32, wall.row as f32, color, width);
+    } else {
+        draw_row(painter, wall.column as f32, wall.row as f32, color, width);
+    }
+}
+
+// How each move class is marked along a wall, so the classes differ by more than color: the color
+// index in the theme, the stretches of the wall stroked, the stroke width as a share of the line
+// width and the offsets of parallel strokes from the middle of the wall.
+struct MarkStyle {
+    color: usize,
+    dashes: &'static [(f32, f32)],
+    width: f32,
+    offsets: &'static [f32],
+}
+
+const SOLID: &[(f32, f32)] = &[(0.15, 0.85)];
+const CENTERED: &[f32] = &[0.0];
+
+fn mark_style(class: &MoveClass) -> MarkStyle {
+    match class {
+        // Thick.
+        MoveClass::Capture => MarkStyle {
+            color: 0,
+            dashes: SOLID,
+            width: 0.6,
+            offsets: CENTERED,
+        },
+        // Doubled.
+        MoveClass::DoubleCross => MarkStyle {
+            color: 1,
+            dashes: SOLID,
+            width: 0.2,
+            offsets: &[-0.06, 0.06],
+        },
+        // Thin.
+        MoveClass::Safe => MarkStyle {
+            color: 2,
+            dashes: SOLID,
+            width: 0.2,
+            offsets: CENTERED,
+        },
+        // Dotted, with a gap in the middle for the number of boxes.
+        MoveClass::Sacrifice(_) => MarkStyle {
+            color: 3,
+            dashes: &[(0.15, 0.22), (0.3, 0.37), (0.63, 0.7), (0.78, 0.85)],
+            width: 0.3,
+            offsets: CENTERED,
+        },
+        // Dashed.
+        MoveClass::Loony => MarkStyle {
+            color: 4,
+            dashes: &[(0.15, 0.3), (0.425, 0.575), (0.7, 0.85)],
+            width: 0.3,
+            offsets: CENTERED,
+        },
+    }
+}
+
+fn draw_mark<P: Painter>(
+    painter: &mut P,
+    layer: u64,
+    from: (f32, f32),
+    is_column: bool,
+    length: f32,
+    style: &MarkStyle,
+    theme: &Theme,
+) {
+    // Stroke a mark along a wall of the given length, down from its start for columns and across
+    // for rows.
+    let color = theme.move_class_stroke[style.color];
+    for &offset in style.offsets {
+        for &(start, end) in style.dashes {
+            let (start, end) = (start * length, end * length);
+            let (from, to) = if is_column {
+                (
+                    (from.0 + offset, from.1 + start),
+                    (from.0 + offset, from.1 + end),
+                )
+            } else {
+                (
+                    (from.0 + start, from.1 + offset),
+                    (from.0 + end, from.1 + offset),
+                )
+            };
+            painter.stroke_line(layer, from, to, theme.line_width * style.width, color);
+        }
+    }
+}
+
+pub fn draw_move_classes<P: Painter>(
+    painter: &mut P,
+    game_grid: &Grid,
+    classes: &HashMap<Wall, MoveClass>,
+    theme: &Theme,
+) {
+    // Mark each unset wall along its middle in the color and style of what the move does, with
+    // the boxes a sacrifice gives away, and a legend of the styles below the status line.
+    for (wall, class) in classes {
+        let from = (wall.column as f32, wall.row as f32);
+        let style = mark_style(class);
+        draw_mark(
+            painter,
+            WALL_LAYER,
+            from,
+            wall.is_column,
+            1.0,
+            &style,
+            theme,
+        );
+        if let MoveClass::Sacrifice(boxes) = class {
+            let middle = if wall.is_column {
+                (from.0, from.1 + 0.5)
+            } else {
+                (from.0 + 0.5, from.1)
+            };
+            painter.text(
+                WALL_LAYER,
+                middle,
+                0.2,
+                &boxes.to_string(),
+                theme.move_class_stroke[style.color],
+            );
+        }
+    }
+
+    // Each entry is a short mark followed by its label, all centered under the grid and shrunk
+    // to the width of the grid when it is narrow.
+    let entries = [
+        (MoveClass::Capture, "capture"),
+        (MoveClass::DoubleCross, "double-cross"),
+        (MoveClass::Safe, "safe"),
+        (MoveClass::Sacrifice(0), "sacrifice of n"),
+        (MoveClass::Loony, "loony"),
+    ];
+    let width = (game_grid.column_count() - 1) as f32;
+    let characters: usize = entries.iter().map(|(_, label)| label.len()).sum();
+    let natural = entries.len() as f32 * 0.55 + characters as f32 * 0.072;
+    let scale = (width / natural).min(1.0);
+    let (text_height, mark_length, gap) = (0.12 * scale, 0.3 * scale, 0.05 * scale);
+    let row = (game_grid.row_count() - 1) as f32 + 0.72;
+    let mut left = width / 2.0 - natural * scale / 2.0;
+    for (class, label) in entries {
+        // The mark is stroked over the middle 70% of its length, like along a wall.
+        let length = mark_length / 0.7;
+        draw_mark(
+            painter,
+            STATUS_LAYER,
+            (left - 0.15 * length, row),
+            false,
+            length,
+            &mark_style(&class),
+            theme,
+        );
+        let label_width = label.len() as f32 * text_height * 0.6;
+        painter.text(
+            STATUS_LAYER,
+            (left + mark_length + gap + label_width / 2.0, row),
+            text_height,
+            label,
+            theme.dot_fill,
+        );
+        left += mark_length + label_width + 5.0 * gap;
+    }
+}
+
+pub fn draw_heatmap<P: Painter>(
+    painter: &mut P,
+    game_grid: &Grid,
+    losses: &HashMap<Wall, i32>,
+    theme: &Theme,
+) {
+    // Color each unset wall on the heatmap gradient by the boxes it loses against the best move,
+    // with a legend of the gradient below the status line.
+    let worst = losses.values().copied().max().unwrap_or(0).max(1);
+    for (wall, &loss) in losses {
+        let color = blend(theme.heatmap_stroke, loss as f32 / worst as f32);
+        if wall.is_column {
+            draw_column(
+                painter,
+                wall.column as f32,
+                wall.row as f32,
+                color,
+                theme.line_width * 0.5,
+            );
+        } else {
+            draw_row(
+                painter,
+                wall.column as f32,
+                wall.row as f32,
+                color,
+                theme.line_width * 0.5,
+            );
+        }
+    }
+
+    let center = (game_grid.column_count() - 1) as f32 / 2.0;
+    let row = (game_grid.row_count() - 1) as f32 + 0.72;
+    painter.text(
+        STATUS_LAYER,
+        (center - 1.3, row),
+        0.12,
+        "Boxes lost",
+        theme.dot_fill,
+    );
+    painter.text(STATUS_LAYER, (center - 0.6, row), 0.12, "0", theme.dot_fill);
+    let step_width = 0.9 / HEATMAP_LEGEND_STEPS as f32;
+    for step in 0..HEATMAP_LEGEND_STEPS {
+        let left = center - 0.45 + step as f32 * step_width;
+        painter.fill_rect(
+            STATUS_LAYER,
+            (left, row - 0.05),
+            (left + step_width, row + 0.05),
+            blend(
+                theme.heatmap_stroke,
+                step as f32 / (HEATMAP_LEGEND_STEPS - 1) as f32,
+            ),
+        );
+    }
+    painter.text(
+        STATUS_LAYER,
+        (center + 0.6, row),
+        0.12,
+        &worst.to_string(),
+        theme.dot_fill,
+    );
+}
+
+fn blend(gradient: [Color; 2], position: f32) -> Color {
+    let (red, green, blue, alpha) = gradient[0].to_rgba_components();
+    let (end_red, end_green, end_blue, end_alpha) = gradient[1].to_rgba_components();
+    let mix = |start: f32, end: f32| start + (end - start) * position;
+    Color::Rgba(
+        mix(red, end_red),
+        mix(green, end_green),
+        mix(blue, end_blue),
+        mix(alpha, end_alpha),
+    )
+}
+
+pub fn draw_cursor<P: Painter>(painter: &mut P, cursor: &Cursor, theme: &Theme) {
+    painter.clear_layer(CURSOR_LAYER);
+    let column = cursor.column as f32;
+    let row = cursor.row as f32;
+    let half_width = theme.optimal_move_width.max(theme.line_width) / 2.0 + 0.05;
+    let (left, top, right, bottom) = if cursor.is_column {
+        (column - half_width, row, column + half_width, row + 1.0)
+    } else {
+        (column, row - half_width, column + 1.0, row + half_width)
+    };
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+    for index in 0..corners.len() {
+        painter.stroke_line(
+            CURSOR_LAYER,
+            corners[index],
+            corners[(index + 1) % corners.len()],
+            0.05,
+            theme.cursor_stroke,
+        );
+    }
+}
+
+pub fn draw_walls<P: Painter>(
+    painter: &mut P,
+    game_grid: &Grid,
+    optimal_moves: &HashSet<Wall>,
+    theme: &Theme,
+) {
+    for column in 0..game_grid.column_count() {
+        for row in 0..game_grid.row_count() {
+            if row < game_grid.row_count() - 1 {
+                // Column lines do not exist in the last row.
+                draw_wall(
+                    painter,
+                    &game_grid.get_wall(true, column, row),
+                    optimal_moves,
+                    theme,
+                );
+            }
+            if column < game_grid.column_count() - 1 {
+                // Row lines do not exist in the last column.
+                draw_wall(
+                    painter,
+                    &game_grid.get_wall(false, column, row),
+                    optimal_moves,
+                    theme,
+                );
+            }
+        }
+    }
+}
+
+pub fn draw_board<P: Painter>(
+    painter: &mut P,
+    game_grid: &Grid,
+    optimal_moves: &HashSet<Wall>,
+    owners: Option<&Owners>,
+    theme: &Theme,
+) {
+    for column in 0..game_grid.column_count() {
+        for row in 0..game_grid.row_count() {
+            if row < game_grid.row_count() - 1 && column < game_grid.column_count() - 1 {
+                // Draw a square.
+                draw_square(
+                    painter,
+                    column as f32,
+                    row as f32,
+                    game_grid.get_wall_count(column, row),
+                    owners.and_then(|owners| owners[column as usize][row as usize]),
+                    theme,
+                );
+            }
+
+            // Draw a dot.
+            draw_dot(painter, column as f32, row as f32, theme);
+        }
+    }
+
+    draw_walls(painter, game_grid, optimal_moves, theme);
+}
+
+pub fn draw_move_numbers<P: Painter>(painter: &mut P, history: &MoveHistory, theme: &Theme) {
+    // Number each set wall at its midpoint in the order it was played.
+    for (index, wall) in history.moves().iter().enumerate() {
+        let center = if wall.is_column {
+            (wall.column as f32, wall.row as f32 + 0.5)
+        } else {
+            (wall.column as f32 + 0.5, wall.row as f32)
+        };
+        painter.text(
+            ANNOTATION_LAYER,
+            center,
+            0.25,
+            &(index + 1).to_string(),
+            theme.dot_fill,
+        );
+    }
+}
+
+pub fn draw_coordinates<P: Painter>(painter: &mut P, game_grid: &Grid, theme: &Theme) {
+    // Label dot columns with letters above the grid and dot rows with numbers left of the grid,
+    // matching the wall notation. A ruler with a tick at every dot runs between the labels and
+    // the grid.
+    let last_column = (game_grid.column_count() - 1) as f32;
+    let last_row = (game_grid.row_count() - 1) as f32;
+    painter.stroke_line(
+        ANNOTATION_LAYER,
+        (0.0, RULER_OFFSET),
+        (last_column, RULER_OFFSET),
+        RULER_WIDTH,
+        theme.line_stroke,
+    );
+    painter.stroke_line(
+        ANNOTATION_LAYER,
+        (RULER_OFFSET, 0.0),
+        (RULER_OFFSET, last_row),
+        RULER_WIDTH,
+        theme.line_stroke,
+    );
+
+    for column in 0..game_grid.column_count() {
+        let column_f = column as f32;
+        painter.stroke_line(
+            ANNOTATION_LAYER,
+            (column_f, RULER_OFFSET - RULER_TICK),
+            (column_f, RULER_OFFSET + RULER_TICK),
+            RULER_WIDTH,
+            theme.line_stroke,
+        );
+        painter.text(
+            ANNOTATION_LAYER,
+            (column_f, LABEL_OFFSET),
+            LABEL_HEIGHT,
+            &column_label(column).to_string(),
+            theme.dot_fill,
+        );
+    }
+    for row in 0..game_grid.row_count() {
+        let row_f = row as f32;
+        painter.stroke_line(
+            ANNOTATION_LAYER,
+            (RULER_OFFSET - RULER_TICK, row_f),
+            (RULER_OFFSET + RULER_TICK, row_f),
+            RULER_WIDTH,
+            theme.line_stroke,
+        );
+        painter.text(
+            ANNOTATION_LAYER,
+            (LABEL_OFFSET, row_f),
+            LABEL_HEIGHT,
+            &row_label(row),
+            theme.dot_fill,
+        );
+    }
+}
@@ -0,0 +1,580 @@
+use crate::engine::parse_dots;
+use crate::game::Game;
+use crate::grid::{Grid, Tier};
+use crate::search::CancelToken;
+use crate::solver::{default_threads, solve, wall_key};
+use crate::tablebase::{symmetries, transform};
+use crate::wall::Wall;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+// Opening books: how games went after each safe move from early positions of one grid size. A
+// position is keyed by a hash of its walls taken over its reflections and rotations, keeping the
+// smallest, and its moves are stored as they appear in the reflection giving that hash. Books are
+// built from bot self-play or, for grids small enough, from the solver, where each move is scored
+// as one game with its exact result and margin.
+//
+// Books are text files:
+//
+//     dots 4x4 plies 2
+//     12ab34cd56ef7890 a1h:40/22/2/31 b1h:38/15/0/-12
+//
+// The header gives the grid size and the number of walls set in the deepest positions the book
+// covers plus one. Each position line has the position's hash in hexadecimal followed by its moves
+// with the games, wins, draws and the sum of the final margins after them, from the point of view
+// of the player making the move. The margin may be left out, counting as 0. Lines starting with
+// "#" are comments.
+//
+// Books for 3x3 and 5x5 boxes are built in. When a book covers the position the advisor narrows
+// safe moves to the book's moves with the best win rate and, among those, the best average margin.
+
+const BOOK_USAGE: &str = "Usage: dots_and_boxes book --dots COLUMNSxROWS --output FILE \
+[--plies COUNT] [--games COUNT] [--seed NUMBER] [--solve] [--threads COUNT]
+Positions with fewer than --plies walls set (3 by default) are covered. Moves are scored by --games \
+games of self-play (1000 by default), or exactly by the solver with --solve.";
+// Moves played fewer times than this in self-play are left out of the book.
+const MINIMUM_GAMES: u32 = 3;
+
+const BUILT_IN: [&str; 2] = [
+    include_str!("../books/4x4.book"),
+    include_str!("../books/6x6.book"),
+];
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    // The sum of the final margins, in boxes.
+    pub margin: i32,
+}
+
+impl MoveStats {
+    pub fn win_rate(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games as f64
+    }
+
+    pub fn average_margin(&self) -> f64 {
+        self.margin as f64 / self.games as f64
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Book {
+    column_count: i32,
+    row_count: i32,
+    // Positions with this many walls set or more are not in the book.
+    plies: usize,
+    positions: HashMap<u64, HashMap<Wall, MoveStats>>,
+}
+
+fn set_walls(game_grid: &Grid) -> Vec<Wall> {
+    Grid::new(game_grid.column_count(), game_grid.row_count())
+        .legal_moves()
+        .filter(|wall| {
+            game_grid
+                .get_wall(wall.is_column, wall.column, wall.row)
+                .set
+        })
+        .collect()
+}
+
+fn canonical_key(game_grid: &Grid) -> (u64, Vec<usize>) {
+    // The position's hash and the symmetries that give it.
+    let (column_count, row_count) = (game_grid.column_count(), game_grid.row_count());
+    let walls = set_walls(game_grid);
+    let keys: Vec<(usize, u64)> = symmetries(column_count, row_count)
+        .into_iter()
+        .map(|symmetry| {
+            let key = walls.iter().fold(0, |key, wall| {
+                key ^ wall_key(
+                    game_grid,
+                    &transform(wall, symmetry, column_count, row_count),
+                )
+            });
+            (symmetry, key)
+        })
+        .collect();
+    let key = keys.iter().map(|&(_, key)| key).min().unwrap();
+    let symmetries = keys
+        .into_iter()
+        .filter(|&(_, other)| other == key)
+        .map(|(symmetry, _)| symmetry)
+        .collect();
+    (key, symmetries)
+}
+
+fn canonical_move(game_grid: &Grid, symmetries: &[usize], wall: &Wall) -> Wall {
+    // The move as stored for the position. A position that is its own reflection has several
+    // symmetries giving its hash, and the move is taken in whichever of them comes first in
+    // notation order, so equivalent moves are stored together.
+    symmetries
+        .iter()
+        .map(|&symmetry| {
+            transform(
+                wall,
+                symmetry,
+                game_grid.column_count(),
+                game_grid.row_count(),
+            )
+        })
+        .min_by_key(|wall| (wall.column, wall.row, wall.is_column))
+        .unwrap()
+}
+
+fn choose(moves: impl IntoIterator<Item = Wall>, rng: &mut StdRng) -> Option<Wall> {
+    // A random move, chosen from the moves in notation order so a seed repeats its games.
+    let mut moves: Vec<Wall> = moves.into_iter().collect();
+    moves.sort_by_key(|wall| (wall.column, wall.row, wall.is_column));
+    moves.choose(rng).copied()
+}
+
+impl Book {
+    pub fn new(column_count: i32, row_count: i32, plies: usize) -> Self {
+        Self {
+            column_count,
+            row_count,
+            plies,
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn column_count(&self) -> i32 {
+        self.column_count
+    }
+
+    pub fn row_count(&self) -> i32 {
+        self.row_count
+    }
+
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn covers(&self, game_grid: &Grid) -> bool {
+        game_grid.column_count() == self.column_count
+            && game_grid.row_count() == self.row_count
+            && set_walls(game_grid).len() < self.plies
+    }
+
+    pub fn record(&mut self, game_grid: &Grid, wall: &Wall, margin: i32) {
+        // Count a game in which the wall was played from the position and the player playing it
+        // finished the given number of boxes ahead.
+        let (key, symmetries) = canonical_key(game_grid);
+        let stats = self
+            .positions
+            .entry(key)
+            .or_default()
+            .entry(canonical_move(game_grid, &symmetries, wall))
+            .or_default();
+        stats.games += 1;
+        stats.margin += margin;
+        if margin > 0 {
+            stats.wins += 1;
+        } else if margin == 0 {
+            stats.draws += 1;
+        }
+    }
+
+    pub fn moves(&self, game_grid: &Grid) -> HashMap<Wall, MoveStats> {
+        // The book's moves for the position, as walls of the grid given.
+        if !self.covers(game_grid) {
+            return HashMap::new();
+        }
+        let (key, symmetries) = canonical_key(game_grid);
+        let Some(stored) = self.positions.get(&key) else {
+            return HashMap::new();
+        };
+        game_grid
+            .legal_moves()
+            .filter_map(|wall| {
+                let stats = stored.get(&canonical_move(game_grid, &symmetries, &wall))?;
+                Some((wall, *stats))
+            })
+            .collect()
+    }
+
+    pub fn from_self_play(
+        column_count: i32,
+        row_count: i32,
+        plies: usize,
+        games: usize,
+        seed: u64,
+    ) -> Self {
+        // Games that open with random safe moves for the given number of plies and continue with
+        // random choices among the advisor's moves.
+        let mut book = Self::new(column_count, row_count, plies);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..games {
+            let mut game = Game::new(column_count, row_count);
+            let mut opening = Vec::new();
+            while !game.is_over() {
+                let game_grid = game.grid();
+                let analysis = game_grid.analyze_heuristics();
+                let in_opening = opening.len() == set_walls(game_grid).len();
+                let wall =
+                    if in_opening && opening.len() < plies && analysis.tier == Some(Tier::Safe) {
+                        let wall = choose(analysis.moves, &mut rng).unwrap();
+                        opening.push((game_grid.clone(), wall, game.player()));
+                        wall
+                    } else {
+                        choose(game_grid.get_optimal_moves(), &mut rng).unwrap()
+                    };
+                game.play(game.player(), &wall).unwrap();
+            }
+            let boxes = game.boxes();
+            for (game_grid, wall, player) in opening {
+                book.record(&game_grid, &wall, boxes[player] - boxes[1 - player]);
+            }
+        }
+        for moves in book.positions.values_mut() {
+            moves.retain(|_, stats| stats.games >= MINIMUM_GAMES);
+        }
+        book.positions.retain(|_, moves| !moves.is_empty());
+        book
+    }
+
+    pub fn from_solver(column_count: i32, row_count: i32, plies: usize, threads: usize) -> Self {
+        // Every position reached by safe moves, with each safe move scored exactly: by an
+        // installed tablebase if there is one and otherwise by the solver. No boxes are completed
+        // before the first move that is not safe, so the score of the rest of the game is the
+        // final margin.
+        let mut book = Self::new(column_count, row_count, plies);
+        let mut positions = vec![Grid::new(column_count, row_count)];
+        for _ in 0..plies {
+            let mut next_positions = Vec::new();
+            for game_grid in &positions {
+                let analysis = game_grid.analyze_heuristics();
+                if analysis.tier != Some(Tier::Safe) {
+                    continue;
+                }
+                let (_, symmetries) = canonical_key(game_grid);
+                let mut scored = HashSet::new();
+                let mut moves: Vec<Wall> = analysis.moves.into_iter().collect();
+                moves.sort_by_key(|wall| (wall.column, wall.row, wall.is_column));
+                for wall in moves {
+                    if !scored.insert(canonical_move(game_grid, &symmetries, &wall)) {
+                        continue;
+                    }
+                    let mut next_grid = game_grid.clone();
+                    next_grid.make_move(&wall);
+                    let score = next_grid.tablebase_score().unwrap_or_else(|| {
+                        solve(&next_grid, threads, &CancelToken::new())
+                            .unwrap()
+                            .score
+                    });
+                    book.record(game_grid, &wall, -score);
+                    next_positions.push(next_grid);
+                }
+            }
+            // Positions reached by more than one path are kept once.
+            let mut seen = HashSet::new();
+            next_positions.retain(|game_grid| seen.insert(canonical_key(game_grid).0));
+            positions = next_positions;
+        }
+        book
+    }
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Positions in hash order and moves in notation order so rebuilt books diff cleanly.
+        writeln!(
+            f,
+            "dots {}x{} plies {}",
+            self.column_count, self.row_count, self.plies
+        )?;
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+        for key in keys {
+            let mut moves: Vec<(&Wall, &MoveStats)> = self.positions[key].iter().collect();
+            moves.sort_by_key(|(wall, _)| (wall.column, wall.row, wall.is_column));
+            write!(f, "{key:016x}")?;
+            for (wall, stats) in moves {
+                write!(
+                    f,
+                    " {wall}:{}/{}/{}/{}",
+                    stats.games, stats.wins, stats.draws, stats.margin
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Book {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        let header: Vec<&str> = match lines.next() {
+            Some((_, line)) => line.split_whitespace().collect(),
+            None => return Err(String::from("The book is empty.")),
+        };
+        let mut book = match header[..] {
+            ["dots", dots, "plies", plies] => {
+                let (column_count, row_count) = parse_dots(dots)?;
+                let plies = plies
+                    .parse()
+                    .map_err(|_| format!("Invalid plies \"{plies}\"."))?;
+                Book::new(column_count, row_count, plies)
+            }
+            _ => {
+                return Err(String::from(
+                    "The book does not start with \"dots COLUMNSxROWS plies COUNT\".",
+                ))
+            }
+        };
+        for (index, line) in lines {
+            let error = |message: &str| format!("Line {}: {message}", index + 1);
+            let mut words = line.split_whitespace();
+            let key = words
+                .next()
+                .and_then(|key| u64::from_str_radix(key, 16).ok())
+                .ok_or_else(|| error("expected a position hash."))?;
+            let mut moves = HashMap::new();
+            for word in words {
+                let (wall, stats) = word
+                    .split_once(':')
+                    .ok_or_else(|| error("expected WALL:GAMES/WINS/DRAWS[/MARGIN]."))?;
+                let wall: Wall = wall.parse().map_err(|message: String| error(&message))?;
+                let counts: Vec<i64> =
+                    stats
+                        .split('/')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| error("expected WALL:GAMES/WINS/DRAWS[/MARGIN]."))?;
+                let (counts, margin) = match counts[..] {
+                    [games, wins, draws] => ([games, wins, draws], 0),
+                    [games, wins, draws, margin] => ([games, wins, draws], margin),
+                    _ => return Err(error("expected WALL:GAMES/WINS/DRAWS[/MARGIN].")),
+                };
+                let stats = match (counts.map(u32::try_from), i32::try_from(margin)) {
+                    ([Ok(games), Ok(wins), Ok(draws)], Ok(margin))
+                        if games > 0
+                            && wins
+                                .checked_add(draws)
+                                .is_some_and(|decided| decided <= games) =>
+                    {
+                        MoveStats {
+                            games,
+                            wins,
+                            draws,
+                            margin,
+                        }
+                    }
+                    _ => return Err(error(&format!("invalid counts for {wall}."))),
+                };
+                moves.insert(wall, stats);
+            }
+            book.positions.insert(key, moves);
+        }
+        Ok(book)
+    }
+}
+
+fn installed() -> &'static RwLock<Vec<Arc<Book>>> {
+    static INSTALLED: OnceLock<RwLock<Vec<Arc<Book>>>> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        RwLock::new(
+            BUILT_IN
+                .iter()
+                .map(|text| Arc::new(text.parse().unwrap()))
+                .collect(),
+        )
+    })
+}
+
+pub fn install(book: Book) {
+    // Replaces any installed book for the same grid size, including a built-in one.
+    let mut books = installed().write().unwrap();
+    books.retain(|other| {
+        (other.column_count, other.row_count) != (book.column_count, book.row_count)
+    });
+    books.push(Arc::new(book));
+}
+
+pub fn find(column_count: i32, row_count: i32) -> Option<Arc<Book>> {
+    installed()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|book| (book.column_count, book.row_count) == (column_count, row_count))
+        .cloned()
+}
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    let mut dots = None;
+    let mut output = None;
+    let mut plies = 3;
+    let mut games = 1000;
+    let mut seed = 0;
+    let mut solver = false;
+    let mut threads = default_threads();
+
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "--solve" {
+            solver = true;
+            continue;
+        }
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("Missing value for {argument}.\n{BOOK_USAGE}"))?;
+        let invalid = || format!("Invalid {argument} \"{value}\".\n{BOOK_USAGE}");
+        match argument.as_str() {
+            "--dots" => {
+                dots = Some(parse_dots(value).map_err(|error| format!("{error}\n{BOOK_USAGE}"))?)
+            }
+            "--output" => output = Some(value),
+            "--plies" => plies = value.parse().map_err(|_| invalid())?,
+            "--games" => games = value.parse().map_err(|_| invalid())?,
+            "--seed" => seed = value.parse().map_err(|_| invalid())?,
+            "--threads" => {
+                threads = match value.parse() {
+                    Ok(count) if count > 0 => count,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(String::from(BOOK_USAGE)),
+        }
+    }
+    let (Some((column_count, row_count)), Some(output)) = (dots, output) else {
+        return Err(String::from(BOOK_USAGE));
+    };
+
+    let book = if solver {
+        Book::from_solver(column_count, row_count, plies, threads)
+    } else {
+        Book::from_self_play(column_count, row_count, plies, games, seed)
+    };
+    fs::write(output, book.to_string())
+        .map_err(|error| format!("Unable to write {output}: {error}."))?;
+    println!("Wrote {} positions to {output}.", book.position_count());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(column_count: i32, row_count: i32, moves: &[&str]) -> Grid {
+        let mut game_grid = Grid::new(column_count, row_count);
+        for notation in moves {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        game_grid
+    }
+
+    #[test]
+    fn finds_moves_in_reflected_positions() {
+        let mut book = Book::new(4, 3, 2);
+        book.record(&grid(4, 3, &["a1h"]), &"b2v".parse().unwrap(), 2);
+        book.record(&grid(4, 3, &["a1h"]), &"b2v".parse().unwrap(), 0);
+        // The same position and move with the columns flipped.
+        let moves = book.moves(&grid(4, 3, &["c1h"]));
+        let stats = moves[&"c2v".parse().unwrap()];
+        assert_eq!(
+            stats,
+            MoveStats {
+                games: 2,
+                wins: 1,
+                draws: 1,
+                margin: 2
+            }
+        );
+        assert_eq!(stats.win_rate(), 0.75);
+        assert_eq!(stats.average_margin(), 1.0);
+        assert!(book.moves(&grid(4, 3, &["a2h"])).is_empty());
+        assert!(book.moves(&grid(4, 3, &["a1h", "a2h"])).is_empty());
+
+        let text = book.to_string();
+        assert_eq!(text.parse::<Book>().unwrap(), book);
+        assert!("dots 4x4\n".parse::<Book>().is_err());
+        assert!("dots 4x4 plies 2\n0 a1h:1/2/0\n".parse::<Book>().is_err());
+        assert!("dots 4x4 plies 2\n0 a1h:1/0/0/1/1\n"
+            .parse::<Book>()
+            .is_err());
+        assert!("dots 4x4 plies 2\n0 a1h:-1/0/0\n".parse::<Book>().is_err());
+        // Books without margins count them as 0.
+        let old = "dots 4x3 plies 2\n0 a1h:2/1/1\n".parse::<Book>().unwrap();
+        assert_eq!(old.positions[&0][&"a1h".parse().unwrap()].margin, 0);
+    }
+
+    #[test]
+    fn solver_books_give_exact_results() {
+        let book = Book::from_solver(3, 3, 1, 1);
+        let game_grid = Grid::new(3, 3);
+        let moves = book.moves(&game_grid);
+        assert!(!moves.is_empty());
+        for (wall, stats) in moves {
+            let mut next_grid = game_grid.clone();
+            next_grid.make_move(&wall);
+            let score = -solve(&next_grid, 1, &CancelToken::new()).unwrap().score;
+            assert_eq!(stats.games, 1);
+            assert_eq!(stats.wins == 1, score > 0);
+            assert_eq!(stats.draws == 1, score == 0);
+            assert_eq!(stats.margin, score);
+        }
+    }
+
+    #[test]
+    fn the_advisor_narrows_safe_moves_to_the_built_in_books() {
+        for (column_count, row_count) in [(4, 4), (6, 6)] {
+            let game_grid = Grid::new(column_count, row_count);
+            let book = find(column_count, row_count).unwrap();
+            let analysis = game_grid.analyze();
+            let book_moves = book.moves(&game_grid);
+            assert!(!book_moves.is_empty());
+            assert_eq!(analysis.win_rates.len(), book_moves.len());
+            // Only the moves with the best win rate and, among those, the best margin are left.
+            let key = |stats: &MoveStats| (stats.win_rate(), stats.average_margin());
+            let best = book_moves
+                .values()
+                .map(key)
+                .max_by(|a, b| a.partial_cmp(b).unwrap())
+                .unwrap();
+            for wall in &analysis.moves {
+                assert_eq!(key(&book_moves[wall]), best);
+            }
+            let best_count = book_moves
+                .values()
+                .filter(|stats| key(stats) == best)
+                .count();
+            assert_eq!(analysis.moves.len(), best_count);
+        }
+    }
+
+    #[test]
+    fn the_built_in_books_record_margins() {
+        for (column_count, row_count) in [(4, 4), (6, 6)] {
+            let book = find(column_count, row_count).unwrap();
+            // Books written before margins were recorded read as zero margins throughout.
+            let empty_grid = Grid::new(column_count, row_count);
+            assert!(book
+                .moves(&empty_grid)
+                .values()
+                .all(|stats| stats.margin != 0));
+        }
+    }
+
+    #[test]
+    fn rejects_counts_that_overflow() {
+        let text = format!(
+            "dots 3x3 plies 1\n0000000000000000 a1h:1/{0}/{0}\n",
+            u32::MAX
+        );
+        assert!(text.parse::<Book>().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::grid::Grid;
+use crate::wall::Wall;
+use std::collections::HashMap;
+use std::fmt;
+
+// What each legal wall does for the player setting it. A move that does not complete a box either
+// gives nothing away (safe) or offers the boxes that can then be taken in turn. The offer is loony
+// when the opponent can choose between taking every box and declining the last two (four in a
+// loop) with a double-dealing move, leaving the mover to take them with a double-cross and move
+// again: any offer of two or more boxes in a row that end on the edge or at another square, or of
+// a whole loop. Opening a two chain in the middle (the hard-hearted handout) offers two single
+// boxes, which cannot be declined, so it is a plain sacrifice.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MoveClass {
+    // Completes one box.
+    Capture,
+    // Completes two boxes with one wall.
+    DoubleCross,
+    // Gives no box away.
+    Safe,
+    // Gives the opponent this many boxes without a choice.
+    Sacrifice(i32),
+    // Gives the opponent the choice of taking everything or keeping control.
+    Loony,
+}
+
+impl fmt::Display for MoveClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveClass::Capture => write!(f, "capture"),
+            MoveClass::DoubleCross => write!(f, "double-cross"),
+            MoveClass::Safe => write!(f, "safe"),
+            MoveClass::Sacrifice(boxes) => write!(f, "sacrifice of {boxes}"),
+            MoveClass::Loony => write!(f, "loony"),
+        }
+    }
+}
+
+pub fn classify_moves(game_grid: &Grid) -> HashMap<Wall, MoveClass> {
+    game_grid
+        .legal_moves()
+        .map(|wall| (wall, classify_move(game_grid, &wall)))
+        .collect()
+}
+
+pub fn classify_move(game_grid: &Grid, wall: &Wall) -> MoveClass {
+    let mut next_grid = game_grid.clone();
+    match next_grid.make_move(wall).completed {
+        2 => return MoveClass::DoubleCross,
+        1 => return MoveClass::Capture,
+        _ => {}
+    }
+    let offers = offers(&next_grid);
+    if offers.is_empty() {
+        MoveClass::Safe
+    } else if offers
+        .iter()
+        .any(|&(boxes, closed)| boxes >= 2 && !closed || boxes > 2 && closed)
+    {
+        MoveClass::Loony
+    } else {
+        MoveClass::Sacrifice(offers.iter().map(|&(boxes, _)| boxes).sum())
+    }
+}
+
+pub(crate) fn offers(game_grid: &Grid) -> Vec<(i32, bool)> {
+    // The runs of boxes the player to move can take one after another, each with whether its last
+    // wall completes two boxes (the run went around a loop or met another run).
+    let mut game_grid = game_grid.clone();
+    let columns = game_grid.column_count() - 1;
+    let rows = game_grid.row_count() - 1;
+    let in_grid = |column: i32, row: i32| column >= 0 && column < columns && row >= 0 && row < rows;
+    let mut offers = Vec::new();
+    for start_column in 0..columns {
+        for start_row in 0..rows {
+            let (mut column, mut row) = (start_column, start_row);
+            let mut boxes = 0;
+            let mut closed = false;
+            while in_grid(column, row) && game_grid.get_wall_count(column, row) == 3 {
+                // Take the box and move on to the square across its last wall.
+                let square_walls = game_grid.get_square_walls(column, row);
+                let wall = [
+                    square_walls.top,
+                    square_walls.right,
+                    square_walls.bottom,
+                    square_walls.left,
+                ]
+                .into_iter()
+                .find(|wall| !wall.set)
+                .unwrap();
+                game_grid.set_wall_with_wall(&wall, true);
+                boxes += 1;
+                let Some(next) = game_grid
+                    .adjacent_squares(&wall)
+                    .into_iter()
+                    .find(|&square| square != (column, row))
+                else {
+                    break;
+                };
+                (column, row) = next;
+                if game_grid.get_wall_count(column, row) == 4 {
+                    boxes += 1;
+                    closed = true;
+                    break;
+                }
+            }
+            if boxes > 0 {
+                offers.push((boxes, closed));
+            }
+        }
+    }
+    offers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with(column_count: i32, row_count: i32, notations: &[&str]) -> Grid {
+        let mut game_grid = Grid::new(column_count, row_count);
+        for notation in notations {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        game_grid
+    }
+
+    fn class(game_grid: &Grid, notation: &str) -> MoveClass {
+        classify_move(game_grid, &notation.parse().unwrap())
+    }
+
+    #[test]
+    fn classifies_captures_and_safe_moves() {
+        let classes = classify_moves(&Grid::new(3, 3));
+        assert_eq!(classes.len(), 12);
+        assert!(classes.values().all(|&class| class == MoveClass::Safe));
+
+        let game_grid = grid_with(3, 3, &["a1h", "a1v"]);
+        assert_eq!(class(&game_grid, "a2h"), MoveClass::Sacrifice(1));
+        let game_grid = grid_with(3, 3, &["a1h", "a1v", "a2h"]);
+        assert_eq!(class(&game_grid, "b1v"), MoveClass::Capture);
+        let game_grid = grid_with(3, 3, &["a1h", "a1v", "a2h", "b1h", "c1v", "b2h"]);
+        assert_eq!(class(&game_grid, "b1v"), MoveClass::DoubleCross);
+    }
+
+    #[test]
+    fn tells_loony_moves_from_sacrifices() {
+        // Opening a two chain at the end lets the opponent decline it, but not in the middle.
+        let game_grid = grid_with(3, 2, &["a1h", "b1h", "a2h", "b2h"]);
+        assert_eq!(class(&game_grid, "b1v"), MoveClass::Sacrifice(2));
+        assert_eq!(class(&game_grid, "a1v"), MoveClass::Loony);
+        assert_eq!(class(&game_grid, "c1v"), MoveClass::Loony);
+
+        // Every move opens the loop inside the walls around a 2x2 box grid.
+        let game_grid = grid_with(
+            3,
+            3,
+            &["a1h", "b1h", "c1v", "c2v", "a3h", "b3h", "a1v", "a2v"],
+        );
+        let classes = classify_moves(&game_grid);
+        assert_eq!(classes.len(), 4);
+        assert!(classes.values().all(|&class| class == MoveClass::Loony));
+    }
+}
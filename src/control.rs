@@ -0,0 +1,278 @@
+use crate::grid::{Grid, Tier};
+use crate::wall::Wall;
+use std::collections::HashMap;
+
+// Controlled values of loony endgames, where no safe moves are left and every unfinished square has
+// two walls, so the squares form separate chains (ending on the edge of the grid) and loops. The
+// player to move has to open a component and the other player takes it. On a long chain (three or
+// more boxes) or a loop (four or more) the player taking it can instead decline the last two boxes
+// of the chain or the last four of the loop, so the opener has to open the next component too.
+// That is keeping control, and it costs four boxes of margin on a chain and eight on a loop.
+//
+// The fully controlled value is the margin of the player in control over the long chains and loops
+// if they keep control on every one of them, and the controlled value adds back the terminal bonus
+// for taking all of the last one: four if any chains are left, since the opener leaves one of them
+// for last, and eight if only loops are. Short chains cannot be declined, so each one hands control
+// to the other player, and the player in control is the one who does not have to open the first
+// long component once the short chains are gone.
+//
+// The predicted margin plays the endgame exactly: the opener picks the component to open and the
+// other player keeps control or takes everything and gives control away, whichever is better.
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ControlledValue {
+    // Component lengths in boxes, longest first.
+    pub long_chains: Vec<i32>,
+    pub loops: Vec<i32>,
+    pub short_chains: Vec<i32>,
+    // Whether the player to move is the player in control.
+    pub mover_in_control: bool,
+    pub controlled_value: i32,
+    pub fully_controlled_value: i32,
+    // The final margin over the remaining boxes for the player in control with best play.
+    pub margin: i32,
+    // Whether best play gives up control before the last component rather than keeping it.
+    pub give_up_control: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub(crate) enum Component {
+    Chain(i32),
+    Loop(i32),
+}
+
+pub fn controlled_value(game_grid: &Grid) -> Option<ControlledValue> {
+    // None unless the position is a loony endgame made of chains and loops.
+    if game_grid.analyze_heuristics().tier != Some(Tier::Sacrifice) {
+        return None;
+    }
+    let simple = (0..game_grid.column_count() - 1).all(|column| {
+        (0..game_grid.row_count() - 1)
+            .all(|row| matches!(game_grid.get_wall_count(column, row), 2 | 4))
+    });
+    if !simple {
+        return None;
+    }
+    let mut components = find_components(game_grid);
+    components.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut long_chains = Vec::new();
+    let mut loops = Vec::new();
+    let mut short_chains = Vec::new();
+    for component in &components {
+        match *component {
+            Component::Chain(length) if length < 3 => short_chains.push(length),
+            Component::Chain(length) => long_chains.push(length),
+            Component::Loop(length) => loops.push(length),
+        }
+    }
+    let long_boxes: i32 = long_chains.iter().chain(&loops).sum();
+    let fully_controlled_value = long_boxes - 4 * long_chains.len() as i32 - 8 * loops.len() as i32;
+    let terminal_bonus = if !long_chains.is_empty() {
+        4
+    } else if !loops.is_empty() {
+        8
+    } else {
+        0
+    };
+
+    // Each short chain passes the obligation to open to the other player.
+    let mover_in_control = short_chains.len() % 2 == 1;
+    let for_controller = |margin: i32| if mover_in_control { -margin } else { margin };
+    let margin = for_controller(endgame_margin(&components, true, &mut HashMap::new()));
+    let keeping = for_controller(endgame_margin(&components, false, &mut HashMap::new()));
+    Some(ControlledValue {
+        long_chains,
+        loops,
+        short_chains,
+        mover_in_control,
+        controlled_value: fully_controlled_value + terminal_bonus,
+        fully_controlled_value,
+        margin,
+        give_up_control: margin > keeping,
+    })
+}
+
+pub(crate) fn find_components(game_grid: &Grid) -> Vec<Component> {
+    // The chains and loops of the squares with two walls. Chains end on the edge of the grid or at
+    // a square with fewer walls, where other chains may meet.
+    let columns = game_grid.column_count() - 1;
+    let rows = game_grid.row_count() - 1;
+    let unset_walls = |column: i32, row: i32| {
+        let square_walls = game_grid.get_square_walls(column, row);
+        [
+            square_walls.top,
+            square_walls.right,
+            square_walls.bottom,
+            square_walls.left,
+        ]
+        .into_iter()
+        .filter(|wall| !wall.set)
+        .collect::<Vec<Wall>>()
+    };
+    // The square across a wall, or None for the edge of the grid.
+    let across = |wall: &Wall, column: i32, row: i32| {
+        game_grid
+            .adjacent_squares(wall)
+            .into_iter()
+            .find(|&square| square != (column, row))
+    };
+
+    let mut visited = vec![vec![false; rows as usize]; columns as usize];
+    let mut components = Vec::new();
+    for column in 0..columns {
+        for row in 0..rows {
+            if game_grid.get_wall_count(column, row) != 2 || visited[column as usize][row as usize]
+            {
+                continue;
+            }
+            visited[column as usize][row as usize] = true;
+
+            // Follow the squares out of both unset walls, until the end of the chain or back here.
+            let mut length = 1;
+            let mut is_loop = false;
+            for wall in unset_walls(column, row) {
+                let mut previous = wall;
+                let mut square = across(&wall, column, row);
+                while let Some((next_column, next_row)) = square {
+                    if (next_column, next_row) == (column, row) {
+                        is_loop = true;
+                        break;
+                    }
+                    if game_grid.get_wall_count(next_column, next_row) != 2 {
+                        break;
+                    }
+                    visited[next_column as usize][next_row as usize] = true;
+                    length += 1;
+                    let wall = *unset_walls(next_column, next_row)
+                        .iter()
+                        .find(|&&wall| wall != previous)
+                        .unwrap();
+                    previous = wall;
+                    square = across(&wall, next_column, next_row);
+                }
+                if is_loop {
+                    break;
+                }
+            }
+            components.push(if is_loop {
+                Component::Loop(length)
+            } else {
+                Component::Chain(length)
+            });
+        }
+    }
+    components
+}
+
+fn endgame_margin(
+    components: &[Component],
+    can_give_up: bool,
+    memo: &mut HashMap<Vec<Component>, i32>,
+) -> i32 {
+    // The final margin of the player not to move, who takes whatever the player to move opens,
+    // with the components sorted. Without giving up control, the player taking a long component
+    // keeps control on all but the last one.
+    if components.is_empty() {
+        return 0;
+    }
+    if let Some(&margin) = memo.get(components) {
+        return margin;
+    }
+    let mut best = i32::MAX;
+    for index in 0..components.len() {
+        if index > 0 && components[index] == components[index - 1] {
+            continue;
+        }
+        let mut rest = components.to_vec();
+        rest.remove(index);
+        let rest_margin = endgame_margin(&rest, can_give_up, memo);
+        let (length, cost) = match components[index] {
+            Component::Chain(length) if length < 3 => (length, None),
+            Component::Chain(length) => (length, Some(4)),
+            Component::Loop(length) => (length, Some(8)),
+        };
+        let take_all = length - rest_margin;
+        let margin = match cost {
+            None => take_all,
+            Some(_) if rest.is_empty() => take_all,
+            Some(cost) if can_give_up => take_all.max(length - cost + rest_margin),
+            Some(cost) => length - cost + rest_margin,
+        };
+        best = best.min(margin);
+    }
+    memo.insert(components.to_vec(), best);
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::CancelToken;
+    use crate::solver::solve;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    #[test]
+    fn values_chains_and_loops() {
+        // The outside walls of a 2x2 box grid leave a loop of four.
+        let mut game_grid = Grid::new(3, 3);
+        for notation in ["a1h", "b1h", "c1v", "c2v", "a3h", "b3h", "a1v", "a2v"] {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        assert_eq!(
+            controlled_value(&game_grid),
+            Some(ControlledValue {
+                long_chains: Vec::new(),
+                loops: vec![4],
+                short_chains: Vec::new(),
+                mover_in_control: false,
+                controlled_value: 4,
+                fully_controlled_value: -4,
+                margin: 4,
+                give_up_control: false,
+            })
+        );
+
+        // Any safe move left means the endgame has not started.
+        assert_eq!(controlled_value(&Grid::new(3, 3)), None);
+    }
+
+    #[test]
+    fn predicts_the_solved_margin() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut tested = 0;
+        while tested < 20 {
+            let mut game_grid = Grid::new(4, 4);
+            let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+            walls.shuffle(&mut rng);
+            for wall in &walls {
+                if game_grid.analyze_heuristics().tier != Some(Tier::Safe) {
+                    break;
+                }
+                let outcome = game_grid.clone().make_move(wall);
+                if outcome.completed == 0 && game_grid.analyze_heuristics().moves.contains(wall) {
+                    game_grid.make_move(wall);
+                }
+            }
+            let Some(value) = controlled_value(&game_grid) else {
+                continue;
+            };
+            tested += 1;
+            let score = solve(&game_grid, 1, &CancelToken::new()).unwrap().score;
+            let expected = if value.mover_in_control {
+                score
+            } else {
+                -score
+            };
+            assert_eq!(value.margin, expected, "{game_grid}");
+            if value.short_chains.is_empty() {
+                // Keeping control is worth the controlled value whatever order the chains and
+                // loops are opened in.
+                assert!(value.margin >= value.controlled_value);
+                assert_eq!(value.give_up_control, value.margin > value.controlled_value);
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::grid::Grid;
+use crate::wall::Wall;
+
+// The safe move countdown: the most safe moves that can still be played before someone has to give
+// away a box. A set of walls can all be played safely, in any order, exactly when no square ends up
+// with more than two walls, so the count is the largest such set of unset walls. Walls inside the
+// grid join two squares, which always have opposite colors on a checkerboard, so this is a
+// bipartite matching problem solved as a maximum flow:
+//
+//     source -> dark square       the walls the square can still take (two minus its walls)
+//     dark square -> light square one for each unset wall between them
+//     dark square -> sink         one for each unset wall on the edge of the grid
+//     source -> light square      one for each unset wall on the edge of the grid
+//     light square -> sink        the walls the square can still take
+//
+// Safe moves do not complete boxes, so the players alternate and the parity of the count decides
+// who runs out of safe moves first: with an even count the player to move opens the first chain.
+// Boxes that can be completed are counted as taken first, since the player to move takes them and
+// keeps the turn.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Countdown {
+    pub safe_moves: i32,
+    // Whether the player to move has to open the first chain if both players play safe moves.
+    pub mover_opens: bool,
+}
+
+struct Network {
+    // Edges as (to, capacity), with each edge's reverse at the index one above or below it.
+    edges: Vec<(usize, i32)>,
+    adjacent: Vec<Vec<usize>>,
+}
+
+impl Network {
+    fn new(node_count: usize) -> Self {
+        Self {
+            edges: Vec::new(),
+            adjacent: vec![Vec::new(); node_count],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i32) {
+        if capacity <= 0 {
+            return;
+        }
+        self.adjacent[from].push(self.edges.len());
+        self.edges.push((to, capacity));
+        self.adjacent[to].push(self.edges.len());
+        self.edges.push((from, 0));
+    }
+
+    fn augment(&mut self, node: usize, sink: usize, visited: &mut [bool]) -> bool {
+        // Push one unit along a path from node to the sink, if there is one.
+        if node == sink {
+            return true;
+        }
+        visited[node] = true;
+        for index in 0..self.adjacent[node].len() {
+            let edge = self.adjacent[node][index];
+            let (to, capacity) = self.edges[edge];
+            if capacity > 0 && !visited[to] && self.augment(to, sink, visited) {
+                self.edges[edge].1 -= 1;
+                self.edges[edge ^ 1].1 += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn maximum_flow(&mut self, source: usize, sink: usize) -> i32 {
+        let mut flow = 0;
+        while self.augment(source, sink, &mut vec![false; self.adjacent.len()]) {
+            flow += 1;
+        }
+        flow
+    }
+}
+
+pub fn countdown(game_grid: &Grid) -> Countdown {
+    let mut game_grid = game_grid.clone();
+    let mut capturing = true;
+    while capturing {
+        capturing = false;
+        for wall in game_grid.legal_moves().collect::<Vec<Wall>>() {
+            if game_grid.clone().make_move(&wall).completed > 0 {
+                game_grid.make_move(&wall);
+                capturing = true;
+            }
+        }
+    }
+
+    let columns = game_grid.column_count() - 1;
+    let rows = game_grid.row_count() - 1;
+    let square = |column: i32, row: i32| (column * rows + row) as usize;
+    let source = (columns * rows) as usize;
+    let sink = source + 1;
+    let mut network = Network::new(sink + 1);
+
+    // The walls each square can take, and its unset walls on the edge of the grid.
+    let capacity = |column: i32, row: i32| (2 - game_grid.get_wall_count(column, row)).max(0);
+    for column in 0..columns {
+        for row in 0..rows {
+            let square_walls = game_grid.get_square_walls(column, row);
+            let edge_walls = [
+                (row == 0, square_walls.top.set),
+                (column == columns - 1, square_walls.right.set),
+                (row == rows - 1, square_walls.bottom.set),
+                (column == 0, square_walls.left.set),
+            ]
+            .iter()
+            .filter(|&&(on_edge, set)| on_edge && !set)
+            .count() as i32;
+            if (column + row) % 2 == 0 {
+                network.add_edge(source, square(column, row), capacity(column, row));
+                network.add_edge(square(column, row), sink, edge_walls);
+                // Walls inside the grid, to the light squares around it.
+                if column + 1 < columns && !square_walls.right.set {
+                    network.add_edge(square(column, row), square(column + 1, row), 1);
+                }
+                if row + 1 < rows && !square_walls.bottom.set {
+                    network.add_edge(square(column, row), square(column, row + 1), 1);
+                }
+                if column > 0 && !square_walls.left.set {
+                    network.add_edge(square(column, row), square(column - 1, row), 1);
+                }
+                if row > 0 && !square_walls.top.set {
+                    network.add_edge(square(column, row), square(column, row - 1), 1);
+                }
+            } else {
+                network.add_edge(source, square(column, row), edge_walls);
+                network.add_edge(square(column, row), sink, capacity(column, row));
+            }
+        }
+    }
+
+    let safe_moves = network.maximum_flow(source, sink);
+    Countdown {
+        safe_moves,
+        mover_opens: safe_moves % 2 == 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Tier;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn largest_safe_set(game_grid: &Grid) -> i32 {
+        // The count by trying every set of unset walls.
+        let unset: Vec<Wall> = game_grid.legal_moves().collect();
+        let mut best = 0;
+        for set in 0..1u32 << unset.len() {
+            let walls: Vec<&Wall> = (0..unset.len())
+                .filter(|bit| set & (1 << bit) != 0)
+                .map(|bit| &unset[bit])
+                .collect();
+            let mut next_grid = game_grid.clone();
+            for wall in &walls {
+                next_grid.make_move(wall);
+            }
+            let safe = walls.iter().all(|wall| {
+                next_grid
+                    .adjacent_squares(wall)
+                    .into_iter()
+                    .all(|(column, row)| next_grid.get_wall_count(column, row) <= 2)
+            });
+            if safe {
+                best = best.max(walls.len() as i32);
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn counts_the_longest_run_of_safe_moves() {
+        // Every square of an empty 2x2 box grid can take its two walls on the edge of the grid.
+        let countdown = countdown(&Grid::new(3, 3));
+        assert_eq!(
+            countdown,
+            Countdown {
+                safe_moves: 8,
+                mover_opens: true
+            }
+        );
+
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut tested = 0;
+        while tested < 20 {
+            let mut game_grid = Grid::new(4, 3);
+            let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+            walls.shuffle(&mut rng);
+            for wall in &walls[..6] {
+                game_grid.make_move(wall);
+            }
+            if game_grid.analyze_heuristics().tier == Some(Tier::Capture) {
+                continue;
+            }
+            tested += 1;
+            assert_eq!(
+                super::countdown(&game_grid).safe_moves,
+                largest_safe_set(&game_grid)
+            );
+        }
+    }
+
+    #[test]
+    fn takes_the_boxes_that_can_be_completed_first() {
+        // Completing the top left box sets a2h, leaving the square below it one wall to take.
+        let mut game_grid = Grid::new(3, 3);
+        for notation in ["a1h", "a1v", "b1v"] {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        assert_eq!(
+            countdown(&game_grid),
+            Countdown {
+                safe_moves: 4,
+                mover_opens: true
+            }
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::grid::Grid;
+
+#[derive(PartialEq, Copy, Clone)]
+pub struct Cursor {
+    pub is_column: bool,
+    pub column: i32,
+    pub row: i32,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cursor {
+    pub fn new() -> Self {
+        Self {
+            is_column: false,
+            column: 0,
+            row: 0,
+        }
+    }
+
+    pub fn move_by(&mut self, game_grid: &Grid, column_offset: i32, row_offset: i32) {
+        self.column += column_offset;
+        self.row += row_offset;
+        self.clamp(game_grid);
+    }
+
+    pub fn move_to(&mut self, game_grid: &Grid, is_column: bool, column: i32, row: i32) {
+        self.is_column = is_column;
+        self.column = column;
+        self.row = row;
+        self.clamp(game_grid);
+    }
+
+    pub fn toggle_orientation(&mut self, game_grid: &Grid) {
+        self.is_column = !self.is_column;
+        self.clamp(game_grid);
+    }
+
+    fn clamp(&mut self, game_grid: &Grid) {
+        // Column walls do not exist in the last row and row walls do not exist in the last column.
+        let (last_column, last_row) = if self.is_column {
+            (game_grid.column_count() - 1, game_grid.row_count() - 2)
+        } else {
+            (game_grid.column_count() - 2, game_grid.row_count() - 1)
+        };
+        self.column = self.column.clamp(0, last_column);
+        self.row = self.row.clamp(0, last_row);
+    }
+}
@@ -0,0 +1,473 @@
+use crate::grid::{check_size, Grid};
+use crate::search::{search, CancelToken, SearchLimits};
+use crate::wall::Wall;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread::{self, ScopedJoinHandle};
+use std::time::{Duration, Instant};
+
+// A line based protocol for playing against engines, similar to UCI in chess. Walls are written in
+// move notation (see wall.rs) and every message is a single line.
+//
+//     Controller to engine              Engine to controller
+//     dab                               id name <name>
+//                                       dabok
+//     isready                           readyok
+//     newgame <columns>x<rows>          (the grid size counts dots, e.g. 8x10)
+//     position [moves <wall> ...]       (the walls set since the start of the game, in order)
+//     go [movetime <milliseconds>]      info depth <plies> score <boxes> [nodes <count> pv <wall> ...]
+//        [depth <plies>]                bestmove <wall> (or "bestmove none" when the grid is full)
+//     stop                              (ends a search early with its best move so far)
+//     quit
+//
+// The engine always plays the side to move. Without a depth it plays the advisor's move, ranking
+// safe moves for up to the move time (a second if none is given); with one it searches up to that
+// depth (at least 1) within the move time, or without a time limit if none is given, reporting each
+// completed depth with the nodes searched and the principal variation. Moves are worked out while
+// further commands are read: "stop" and "quit" end a search early and other commands wait for the
+// move. Scores are in boxes from the point of view of the side to move. Problems with a command are
+// reported with "info string <message>" and the command is otherwise ignored.
+
+pub const ENGINE_NAME: &str = "Dots and Boxes Analysis";
+// Time the advisor ranks safe moves for when "go" gives no move time.
+const ADVISOR_TIME: Duration = Duration::from_secs(1);
+// Search time when "go" gives a depth but no move time.
+const UNLIMITED_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+// Time allowed on top of the move time for an external engine to reply.
+const REPLY_GRACE: Duration = Duration::from_secs(5);
+
+pub struct EngineMove {
+    pub wall: Wall,
+    // The last score reported before the move, if any, shown by the window.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub score: Option<i32>,
+}
+
+pub fn run<R: BufRead, W: Write + Send>(input: R, output: W) -> io::Result<()> {
+    // Serve the protocol until "quit" or the end of the input. Moves are worked out on a separate
+    // thread so "stop" can be read while a search runs; any other command waits for the move.
+    let shared = Mutex::new(output);
+    thread::scope(|scope| {
+        let mut game_grid = Grid::new(8, 10);
+        let mut thinking: Option<(ScopedJoinHandle<io::Result<()>>, CancelToken)> = None;
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let command = words.next();
+            if let Some((handle, cancel)) = thinking.take() {
+                if matches!(command, Some("stop") | Some("quit")) {
+                    cancel.cancel();
+                }
+                handle.join().unwrap()?;
+            }
+            let mut output = shared.lock().unwrap();
+            match command {
+                Some("dab") => {
+                    writeln!(output, "id name {ENGINE_NAME}")?;
+                    writeln!(output, "dabok")?;
+                }
+                Some("isready") => writeln!(output, "readyok")?,
+                Some("newgame") => match words.next().map(parse_dots) {
+                    Some(Ok((column_count, row_count))) => {
+                        game_grid = Grid::new(column_count, row_count)
+                    }
+                    Some(Err(error)) => writeln!(output, "info string {error}")?,
+                    None => writeln!(output, "info string Missing grid size.")?,
+                },
+                Some("position") => {
+                    let moves = match words.next() {
+                        Some("moves") | None => words.collect::<Vec<&str>>(),
+                        Some(word) => {
+                            writeln!(output, "info string Expected \"moves\", found \"{word}\".")?;
+                            continue;
+                        }
+                    };
+                    match play_moves(game_grid.column_count(), game_grid.row_count(), &moves) {
+                        Ok(position) => game_grid = position,
+                        Err(error) => writeln!(output, "info string {error}")?,
+                    }
+                }
+                Some("go") => {
+                    let mut move_time = None;
+                    let mut depth = None;
+                    let mut valid = true;
+                    while let Some(word) = words.next() {
+                        let value = words.next();
+                        match word {
+                            "movetime" => match value.and_then(|value| value.parse().ok()) {
+                                Some(milliseconds) => {
+                                    move_time = Some(Duration::from_millis(milliseconds))
+                                }
+                                None => valid = false,
+                            },
+                            "depth" => match value
+                                .and_then(|value| value.parse().ok())
+                                .filter(|&depth: &i32| depth > 0)
+                            {
+                                Some(plies) => depth = Some(plies),
+                                None => valid = false,
+                            },
+                            _ => writeln!(output, "info string Unknown option \"{word}\".")?,
+                        }
+                        if !valid {
+                            let value = value.unwrap_or("");
+                            writeln!(output, "info string Invalid {word} \"{value}\".")?;
+                            break;
+                        }
+                    }
+                    if valid {
+                        let cancel = CancelToken::new();
+                        let token = cancel.clone();
+                        let position = game_grid.clone();
+                        let shared = &shared;
+                        let handle = scope.spawn(move || match depth {
+                            Some(depth) => go_search(shared, &position, move_time, depth, &token),
+                            None => go_advisor(shared, &position, move_time),
+                        });
+                        thinking = Some((handle, cancel));
+                    }
+                }
+                Some("stop") => {}
+                Some("quit") => break,
+                Some(command) => writeln!(output, "info string Unknown command \"{command}\".")?,
+                None => {}
+            }
+            output.flush()?;
+        }
+        // Finish the move being worked out before stopping.
+        match thinking {
+            Some((handle, _)) => handle.join().unwrap(),
+            None => Ok(()),
+        }
+    })
+}
+
+fn go_advisor<W: Write>(
+    output: &Mutex<W>,
+    game_grid: &Grid,
+    move_time: Option<Duration>,
+) -> io::Result<()> {
+    let best = advisor_move(game_grid, move_time.unwrap_or(ADVISOR_TIME));
+    let mut output = output.lock().unwrap();
+    match best {
+        Some((wall, score)) => {
+            writeln!(output, "info depth 1 score {score}")?;
+            writeln!(output, "bestmove {wall}")?;
+        }
+        None => writeln!(output, "bestmove none")?,
+    }
+    output.flush()
+}
+
+fn go_search<W: Write>(
+    output: &Mutex<W>,
+    game_grid: &Grid,
+    move_time: Option<Duration>,
+    depth: i32,
+    cancel: &CancelToken,
+) -> io::Result<()> {
+    let limits = SearchLimits {
+        time: move_time.unwrap_or(UNLIMITED_TIME),
+        depth: Some(depth),
+    };
+    let mut result = Ok(());
+    let best = search(game_grid, limits, cancel, |progress| {
+        if result.is_ok() {
+            let mut line = format!(
+                "info depth {} score {} nodes {} pv",
+                progress.depth, progress.score, progress.nodes
+            );
+            for wall in &progress.principal_variation {
+                line.push_str(&format!(" {wall}"));
+            }
+            let mut output = output.lock().unwrap();
+            result = writeln!(output, "{line}").and_then(|_| output.flush());
+        }
+    });
+    result?;
+    let mut output = output.lock().unwrap();
+    match best.and_then(|best| best.principal_variation.first().copied()) {
+        Some(wall) => writeln!(output, "bestmove {wall}")?,
+        None => writeln!(output, "bestmove none")?,
+    }
+    output.flush()
+}
+
+pub fn parse_dots(text: &str) -> Result<(i32, i32), String> {
+    // A grid size in dots such as "8x10", within the sizes a grid can have.
+    let (column_count, row_count) = text
+        .split_once('x')
+        .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)))
+        .ok_or_else(|| format!("Invalid grid size \"{text}\"."))?;
+    check_size(column_count, row_count)?;
+    Ok((column_count, row_count))
+}
+
+pub fn play_moves(column_count: i32, row_count: i32, moves: &[&str]) -> Result<Grid, String> {
+    let mut game_grid = Grid::new(column_count, row_count);
+    for notation in moves {
+        let wall: Wall = notation.parse()?;
+        if !game_grid.is_valid_wall(&wall) {
+            return Err(format!("Wall {wall} is outside the grid."));
+        }
+        if game_grid
+            .get_wall(wall.is_column, wall.column, wall.row)
+            .set
+        {
+            return Err(format!("Wall {wall} is already set."));
+        }
+        game_grid.set_wall_with_wall(&wall, true);
+    }
+    Ok(game_grid)
+}
+
+pub fn advisor_move(game_grid: &Grid, limit: Duration) -> Option<(Wall, i32)> {
+    // Play the first of the advisor's moves, ranked within the time limit (in notation order so
+    // replies are repeatable), with its score.
+    let mut moves: Vec<Wall> = game_grid.analyze_ranked(limit).moves.into_iter().collect();
+    moves.sort_by_key(|wall| (wall.column, wall.row, wall.is_column));
+    let wall = *moves.first()?;
+    Some((wall, move_score(game_grid, &wall)))
+}
+
+pub fn move_score(game_grid: &Grid, wall: &Wall) -> i32 {
+    // The boxes a move completes or, if it completes none, minus the boxes it gives away.
+    let mut next_grid = game_grid.clone();
+    let completed = next_grid.make_move(wall).completed;
+    if completed > 0 {
+        completed
+    } else {
+        -capturable_boxes(&mut next_grid)
+    }
+}
+
+fn capturable_boxes(game_grid: &mut Grid) -> i32 {
+    // Complete squares with three walls until there are none left, counting the boxes made.
+    let mut boxes = 0;
+    let mut found = true;
+    while found {
+        found = false;
+        for column in 0..(game_grid.column_count() - 1) {
+            for row in 0..(game_grid.row_count() - 1) {
+                if game_grid.get_wall_count(column, row) == 3 {
+                    let wall = game_grid
+                        .get_square_walls(column, row)
+                        .get_first_wall(false);
+                    if let Some(wall) = wall {
+                        boxes += game_grid.make_move(&wall).completed;
+                        found = true;
+                    }
+                }
+            }
+        }
+    }
+    boxes
+}
+
+pub struct ExternalEngine {
+    pub name: String,
+    process: Child,
+    input: ChildStdin,
+    // Lines from the engine, read on a separate thread so replies can time out.
+    output: Receiver<String>,
+}
+
+impl ExternalEngine {
+    pub fn start(command: &str) -> Result<Self, String> {
+        // The command is split on whitespace into the program and its arguments.
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| String::from("The engine command is empty."))?;
+        let mut process = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("Unable to start engine \"{command}\": {error}."))?;
+        let input = process.stdin.take().unwrap();
+        let reader = BufReader::new(process.stdout.take().unwrap());
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            name: String::from(program),
+            process,
+            input,
+            output,
+        };
+        engine.send("dab")?;
+        let deadline = Instant::now() + REPLY_GRACE;
+        loop {
+            let line = engine.receive(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = String::from(name.trim());
+            } else if line.trim() == "dabok" {
+                break;
+            }
+        }
+        Ok(engine)
+    }
+
+    pub fn new_game(&mut self, column_count: i32, row_count: i32) -> Result<(), String> {
+        self.send(&format!("newgame {column_count}x{row_count}"))?;
+        self.send("isready")?;
+        let deadline = Instant::now() + REPLY_GRACE;
+        while self.receive(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn best_move(&mut self, moves: &[Wall], move_time: Duration) -> Result<EngineMove, String> {
+        // Ask for a move in the position reached by setting the walls in order.
+        let mut position = String::from("position moves");
+        for wall in moves {
+            position.push_str(&format!(" {wall}"));
+        }
+        self.send(&position)?;
+        self.send(&format!("go movetime {}", move_time.as_millis()))?;
+
+        let deadline = Instant::now() + move_time + REPLY_GRACE;
+        let mut score = None;
+        loop {
+            let line = self.receive(deadline)?;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("info") => {
+                    while let Some(word) = words.next() {
+                        match word {
+                            "score" => score = words.next().and_then(|value| value.parse().ok()),
+                            "string" => {
+                                println!("{}: {}", self.name, words.collect::<Vec<_>>().join(" "));
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Some("bestmove") => {
+                    let notation = words.next().unwrap_or("none");
+                    let wall = notation.parse().map_err(|error| {
+                        format!("{} replied with an invalid move: {error}", self.name)
+                    })?;
+                    return Ok(EngineMove { wall, score });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.input, "{command}")
+            .and_then(|_| self.input.flush())
+            .map_err(|error| format!("Unable to send to {}: {error}.", self.name))
+    }
+
+    fn receive(&mut self, deadline: Instant) -> Result<String, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.output
+            .recv_timeout(timeout)
+            .map_err(|error| match error {
+                RecvTimeoutError::Timeout => format!("{} did not reply in time.", self.name),
+                RecvTimeoutError::Disconnected => format!("{} has stopped.", self.name),
+            })
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        // Ask the engine to stop, and make sure it does.
+        let _ = self.send("quit");
+        if !matches!(self.process.try_wait(), Ok(Some(_))) {
+            thread::sleep(Duration::from_millis(100));
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(commands: &str) -> String {
+        let mut output = Vec::new();
+        run(commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn handshake() {
+        assert_eq!(
+            reply("dab\nisready\n"),
+            format!("id name {ENGINE_NAME}\ndabok\nreadyok\n")
+        );
+    }
+
+    #[test]
+    fn completes_a_box() {
+        let output = reply("newgame 3x3\nposition moves a1h a1v b1v\ngo movetime 100\n");
+        assert_eq!(output, "info depth 1 score 1\nbestmove a2h\n");
+    }
+
+    #[test]
+    fn searches_to_a_depth() {
+        let output = reply("newgame 3x3\nposition moves a1h a1v b1v\ngo depth 2\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("info depth 1 score 1 nodes "));
+        assert!(lines[1].starts_with("info depth 2 score 1 nodes "));
+        assert!(lines[1].contains(" pv a2h "));
+        assert_eq!(lines[2], "bestmove a2h");
+    }
+
+    #[test]
+    fn full_grid_has_no_move() {
+        let output = reply("newgame 2x2\nposition moves a1h a2h a1v b1v\ngo\n");
+        assert_eq!(output, "bestmove none\n");
+    }
+
+    #[test]
+    fn rejects_invalid_depths() {
+        let output = reply("newgame 3x3\ngo depth 0\ngo depth 4294967297\n");
+        assert_eq!(
+            output,
+            "info string Invalid depth \"0\".\ninfo string Invalid depth \"4294967297\".\n"
+        );
+    }
+
+    #[test]
+    fn stops_a_search() {
+        // Without "stop" the search would carry on to depth 100 for up to a day.
+        let started = Instant::now();
+        let output = reply("newgame 8x8\ngo depth 100\nstop\nisready\n");
+        assert!(started.elapsed() < Duration::from_secs(60));
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[lines.len() - 2].starts_with("bestmove "));
+        assert_eq!(lines[lines.len() - 1], "readyok");
+    }
+
+    #[test]
+    fn accepts_grids_with_more_rows_than_letters() {
+        assert_eq!(parse_dots("3x40"), Ok((3, 40)));
+        assert!(parse_dots("27x3").is_err());
+        assert!(parse_dots("1x3").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let output = reply("newgame 3x3\nposition moves a1h a1h\nposition moves c1h\n");
+        assert_eq!(
+            output,
+            "info string Wall a1h is already set.\ninfo string Wall c1h is outside the grid.\n"
+        );
+    }
+}
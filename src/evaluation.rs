@@ -0,0 +1,110 @@
+use crate::classify::offers;
+use crate::grid::Grid;
+use crate::search::{search, CancelToken, SearchLimits};
+use crate::wall::Wall;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Values of every legal wall for the player setting it, as the boxes they gain, and how much each
+// move loses against the best one. The heuristic counts the boxes the move completes less the boxes
+// it offers the opponent (the size of the chain it sacrifices), and the search looks the given
+// number of plies ahead, counting the move itself.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Evaluator {
+    Heuristic,
+    Search(i32),
+}
+
+// Searches are limited by depth, not time.
+const UNLIMITED_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub fn move_values(game_grid: &Grid, evaluator: Evaluator) -> HashMap<Wall, i32> {
+    game_grid
+        .legal_moves()
+        .map(|wall| {
+            let mut next_grid = game_grid.clone();
+            let outcome = next_grid.make_move(&wall);
+            let rest = match evaluator {
+                Evaluator::Heuristic if outcome.moves_again => 0,
+                Evaluator::Heuristic => -offers(&next_grid)
+                    .iter()
+                    .map(|&(boxes, _)| boxes)
+                    .sum::<i32>(),
+                Evaluator::Search(depth) => {
+                    let limits = SearchLimits {
+                        time: UNLIMITED_TIME,
+                        depth: Some(depth - 1),
+                    };
+                    let score = if depth > 1 {
+                        search(&next_grid, limits, &CancelToken::new(), |_| {})
+                            .map_or(0, |progress| progress.score)
+                    } else {
+                        0
+                    };
+                    if outcome.moves_again {
+                        score
+                    } else {
+                        -score
+                    }
+                }
+            };
+            (wall, outcome.completed + rest)
+        })
+        .collect()
+}
+
+pub fn score_losses(game_grid: &Grid, evaluator: Evaluator) -> HashMap<Wall, i32> {
+    // How many boxes each move is worth less than the best move, so the best moves lose nothing.
+    let values = move_values(game_grid, evaluator);
+    let best = values.values().copied().max().unwrap_or(0);
+    values
+        .into_iter()
+        .map(|(wall, value)| (wall, best - value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::solve;
+
+    fn grid_with(column_count: i32, row_count: i32, notations: &[&str]) -> Grid {
+        let mut game_grid = Grid::new(column_count, row_count);
+        for notation in notations {
+            game_grid.make_move(&notation.parse().unwrap());
+        }
+        game_grid
+    }
+
+    fn loss(losses: &HashMap<Wall, i32>, notation: &str) -> i32 {
+        losses[&notation.parse().unwrap()]
+    }
+
+    #[test]
+    fn losses_count_the_boxes_given_away() {
+        // Taking the box is best, a safe move leaves it to the opponent and a2v gives away both
+        // boxes down the left.
+        let game_grid = grid_with(3, 3, &["a1h", "a1v", "b1v", "a3h"]);
+        let losses = score_losses(&game_grid, Evaluator::Heuristic);
+        assert_eq!(loss(&losses, "a2h"), 0);
+        assert_eq!(loss(&losses, "c1v"), 2);
+        assert_eq!(loss(&losses, "a2v"), 3);
+
+        // Looking ahead, taking the box and then playing safely is no better than the box.
+        let losses = score_losses(&game_grid, Evaluator::Search(2));
+        assert_eq!(loss(&losses, "a2h"), 0);
+        assert_eq!(loss(&losses, "c1v"), 2);
+    }
+
+    #[test]
+    fn searches_agree_with_the_solver_at_full_depth() {
+        let game_grid = grid_with(3, 3, &["a1h", "a1v", "b1v", "b2h", "c2v"]);
+        let values = move_values(&game_grid, Evaluator::Search(12));
+        let solution = solve(&game_grid, 1, &CancelToken::new()).unwrap();
+        assert_eq!(values.values().copied().max(), Some(solution.score));
+        for wall in &solution.best_moves {
+            assert_eq!(values[wall], solution.score);
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use crate::board::{draw_board, draw_coordinates, draw_move_numbers};
+use crate::engine::parse_dots;
+use crate::grid::Grid;
+use crate::history::MoveHistory;
+use crate::painter::Painter;
+use crate::theme::Theme;
+use crate::wall::Wall;
+
+use flo_canvas::Color;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+#[cfg(feature = "gui")]
+use std::path::Path;
+
+// Space around the grid (in grid units) that holds the coordinate labels.
+const EXPORT_MARGIN: f32 = 1.0;
+
+pub struct ExportOptions {
+    pub theme: Theme,
+    pub highlights: bool,
+    pub ownership: bool,
+    pub coordinates: bool,
+    pub move_numbers: bool,
+    // The moves to highlight, or None to ask the advisor for them.
+    pub optimal_moves: Option<HashSet<Wall>>,
+    // Size of a square in pixels (PNG) or user units (SVG).
+    pub square_size: u32,
+}
+
+impl ExportOptions {
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            theme,
+            highlights: true,
+            ownership: true,
+            coordinates: true,
+            move_numbers: false,
+            optimal_moves: None,
+            square_size: 60,
+        }
+    }
+}
+
+enum Shape {
+    Rect((f32, f32), (f32, f32), Color),
+    Circle((f32, f32), f32, Color),
+    Line((f32, f32), (f32, f32), f32, Color),
+    Text((f32, f32), f32, String, Color),
+}
+
+// Records drawing so that it can be replayed in layer order into painters that draw immediately.
+struct Scene {
+    layers: BTreeMap<u64, Vec<Shape>>,
+}
+
+impl Scene {
+    fn replay<P: Painter>(&self, painter: &mut P) {
+        for (layer, shapes) in &self.layers {
+            for shape in shapes {
+                match shape {
+                    Shape::Rect(from, to, color) => painter.fill_rect(*layer, *from, *to, *color),
+                    Shape::Circle(center, radius, color) => {
+                        painter.fill_circle(*layer, *center, *radius, *color)
+                    }
+                    Shape::Line(from, to, width, color) => {
+                        painter.stroke_line(*layer, *from, *to, *width, *color)
+                    }
+                    Shape::Text(center, height, text, color) => {
+                        painter.text(*layer, *center, *height, text, *color)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Painter for Scene {
+    fn clear_layer(&mut self, layer: u64) {
+        self.layers.remove(&layer);
+    }
+
+    fn fill_rect(&mut self, layer: u64, from: (f32, f32), to: (f32, f32), color: Color) {
+        self.layers
+            .entry(layer)
+            .or_default()
+            .push(Shape::Rect(from, to, color));
+    }
+
+    fn fill_circle(&mut self, layer: u64, center: (f32, f32), radius: f32, color: Color) {
+        self.layers
+            .entry(layer)
+            .or_default()
+            .push(Shape::Circle(center, radius, color));
+    }
+
+    fn stroke_line(
+        &mut self,
+        layer: u64,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    ) {
+        self.layers
+            .entry(layer)
+            .or_default()
+            .push(Shape::Line(from, to, width, color));
+    }
+
+    fn text(&mut self, layer: u64, center: (f32, f32), height: f32, text: &str, color: Color) {
+        self.layers.entry(layer).or_default().push(Shape::Text(
+            center,
+            height,
+            String::from(text),
+            color,
+        ));
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    // The text with the characters that are special in XML replaced by entities.
+    let mut escaped = String::new();
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+struct SvgPainter {
+    elements: String,
+}
+
+fn svg_paint(attribute: &str, color: Color) -> String {
+    // Produces e.g. fill="#ff0000" with an opacity attribute for translucent colors.
+    let (red, green, blue, alpha) = color.to_rgba_components();
+    let to_byte = |component: f32| (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut paint = format!(
+        "{attribute}=\"#{:02x}{:02x}{:02x}\"",
+        to_byte(red),
+        to_byte(green),
+        to_byte(blue)
+    );
+    if alpha < 1.0 {
+        let _ = write!(paint, " {attribute}-opacity=\"{alpha}\"");
+    }
+    paint
+}
+
+impl Painter for SvgPainter {
+    fn clear_layer(&mut self, _layer: u64) {}
+
+    fn fill_rect(&mut self, _layer: u64, from: (f32, f32), to: (f32, f32), color: Color) {
+        let _ = writeln!(
+            self.elements,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+            from.0.min(to.0),
+            from.1.min(to.1),
+            (to.0 - from.0).abs(),
+            (to.1 - from.1).abs(),
+            svg_paint("fill", color)
+        );
+    }
+
+    fn fill_circle(&mut self, _layer: u64, center: (f32, f32), radius: f32, color: Color) {
+        let _ = writeln!(
+            self.elements,
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{radius}\" {}/>",
+            center.0,
+            center.1,
+            svg_paint("fill", color)
+        );
+    }
+
+    fn stroke_line(
+        &mut self,
+        _layer: u64,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    ) {
+        let _ = writeln!(
+            self.elements,
+            "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-width=\"{width}\" {}/>",
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            svg_paint("stroke", color)
+        );
+    }
+
+    fn text(&mut self, _layer: u64, center: (f32, f32), height: f32, text: &str, color: Color) {
+        let _ = writeln!(
+            self.elements,
+            "  <text x=\"{}\" y=\"{}\" font-size=\"{height}\" font-family=\"sans-serif\" text-anchor=\"middle\" dominant-baseline=\"central\" {}>{}</text>",
+            center.0,
+            center.1,
+            svg_paint("fill", color),
+            xml_escape(text)
+        );
+    }
+}
+
+struct RasterPainter {
+    pixmap: tiny_skia::Pixmap,
+    transform: tiny_skia::Transform,
+}
+
+fn raster_color(color: Color) -> tiny_skia::Color {
+    let (red, green, blue, alpha) = color.to_rgba_components();
+    tiny_skia::Color::from_rgba(
+        red.clamp(0.0, 1.0),
+        green.clamp(0.0, 1.0),
+        blue.clamp(0.0, 1.0),
+        alpha.clamp(0.0, 1.0),
+    )
+    .unwrap_or(tiny_skia::Color::BLACK)
+}
+
+fn raster_paint(color: Color) -> tiny_skia::Paint<'static> {
+    let mut paint = tiny_skia::Paint::default();
+    paint.set_color(raster_color(color));
+    paint.anti_alias = true;
+    paint
+}
+
+impl Painter for RasterPainter {
+    fn clear_layer(&mut self, _layer: u64) {}
+
+    fn fill_rect(&mut self, _layer: u64, from: (f32, f32), to: (f32, f32), color: Color) {
+        if let Some(rect) = tiny_skia::Rect::from_ltrb(
+            from.0.min(to.0),
+            from.1.min(to.1),
+            from.0.max(to.0),
+            from.1.max(to.1),
+        ) {
+            self.pixmap
+                .fill_rect(rect, &raster_paint(color), self.transform, None);
+        }
+    }
+
+    fn fill_circle(&mut self, _layer: u64, center: (f32, f32), radius: f32, color: Color) {
+        if let Some(path) = tiny_skia::PathBuilder::from_circle(center.0, center.1, radius) {
+            self.pixmap.fill_path(
+                &path,
+                &raster_paint(color),
+                tiny_skia::FillRule::Winding,
+                self.transform,
+                None,
+            );
+        }
+    }
+
+    fn stroke_line(
+        &mut self,
+        _layer: u64,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    ) {
+        let mut path = tiny_skia::PathBuilder::new();
+        path.move_to(from.0, from.1);
+        path.line_to(to.0, to.1);
+        if let Some(path) = path.finish() {
+            let stroke = tiny_skia::Stroke {
+                width,
+                ..tiny_skia::Stroke::default()
+            };
+            self.pixmap
+                .stroke_path(&path, &raster_paint(color), &stroke, self.transform, None);
+        }
+    }
+}
+
+fn draw_scene(game_grid: &Grid, history: &MoveHistory, options: &ExportOptions) -> Scene {
+    let mut scene = Scene {
+        layers: BTreeMap::new(),
+    };
+    let optimal_moves = if options.highlights {
+        options
+            .optimal_moves
+            .clone()
+            .unwrap_or_else(|| game_grid.get_optimal_moves())
+    } else {
+        HashSet::new()
+    };
+    let owners = if options.ownership {
+        Some(history.get_owners(game_grid.column_count(), game_grid.row_count()))
+    } else {
+        None
+    };
+    draw_board(
+        &mut scene,
+        game_grid,
+        &optimal_moves,
+        owners.as_ref(),
+        &options.theme,
+    );
+    if options.coordinates {
+        draw_coordinates(&mut scene, game_grid, &options.theme);
+    }
+    if options.move_numbers {
+        draw_move_numbers(&mut scene, history, &options.theme);
+    }
+    scene
+}
+
+fn image_size(game_grid: &Grid, options: &ExportOptions) -> (f32, f32) {
+    // The grid spans (count - 1) units between the outermost dots.
+    let square_size = options.square_size as f32;
+    (
+        (game_grid.column_count() as f32 - 1.0 + 2.0 * EXPORT_MARGIN) * square_size,
+        (game_grid.row_count() as f32 - 1.0 + 2.0 * EXPORT_MARGIN) * square_size,
+    )
+}
+
+pub fn to_svg(game_grid: &Grid, history: &MoveHistory, options: &ExportOptions) -> String {
+    let scene = draw_scene(game_grid, history, options);
+    let mut svg_painter = SvgPainter {
+        elements: String::new(),
+    };
+    scene.replay(&mut svg_painter);
+
+    let (width, height) = image_size(game_grid, options);
+    let square_size = options.square_size as f32;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"{} {} {} {}\">\n  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>\n{}</svg>\n",
+        -EXPORT_MARGIN,
+        -EXPORT_MARGIN,
+        width / square_size,
+        height / square_size,
+        -EXPORT_MARGIN,
+        -EXPORT_MARGIN,
+        width / square_size,
+        height / square_size,
+        svg_paint("fill", options.theme.background_fill),
+        svg_painter.elements
+    )
+}
+
+pub fn to_png(
+    game_grid: &Grid,
+    history: &MoveHistory,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, String> {
+    let scene = draw_scene(game_grid, history, options);
+    let (width, height) = image_size(game_grid, options);
+    let square_size = options.square_size as f32;
+    let mut pixmap = tiny_skia::Pixmap::new(width.round() as u32, height.round() as u32)
+        .ok_or_else(|| format!("Unable to create a {width}x{height} image."))?;
+    pixmap.fill(raster_color(options.theme.background_fill));
+
+    let mut raster_painter = RasterPainter {
+        pixmap,
+        transform: tiny_skia::Transform::from_row(
+            square_size,
+            0.0,
+            0.0,
+            square_size,
+            EXPORT_MARGIN * square_size,
+            EXPORT_MARGIN * square_size,
+        ),
+    };
+    scene.replay(&mut raster_painter);
+    raster_painter
+        .pixmap
+        .encode_png()
+        .map_err(|error| format!("Unable to encode PNG: {error}."))
+}
+
+pub fn export(
+    path: &str,
+    game_grid: &Grid,
+    history: &MoveHistory,
+    options: &ExportOptions,
+) -> Result<(), String> {
+    let data = if path.ends_with(".svg") {
+        to_svg(game_grid, history, options).into_bytes()
+    } else if path.ends_with(".png") {
+        to_png(game_grid, history, options)?
+    } else {
+        return Err(format!(
+            "Unknown image format for {path} (use .svg or .png)."
+        ));
+    };
+    fs::write(path, data).map_err(|error| format!("Unable to write {path}: {error}."))
+}
+
+const EXPORT_USAGE: &str = "Usage: dots_and_boxes export [--dots COLUMNSxROWS] [--moves \"MOVE ...\"] [--theme NAME|PATH] [--square-size PIXELS] [--no-highlights] [--no-ownership] [--no-coordinates] [--move-numbers] OUTPUT.svg|OUTPUT.png";
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    // Headless export of a position given as a list of moves (for example "a1h a1v b1v").
+    let mut column_count = 8;
+    let mut row_count = 10;
+    let mut moves = String::new();
+    let mut options = ExportOptions::new(Theme::light());
+    let mut output = None;
+
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = || {
+            arguments
+                .next()
+                .ok_or_else(|| format!("Missing value for {argument}.\n{EXPORT_USAGE}"))
+        };
+        match argument.as_str() {
+            "--dots" => {
+                let dots = value()?;
+                (column_count, row_count) =
+                    parse_dots(dots).map_err(|error| format!("{error}\n{EXPORT_USAGE}"))?;
+            }
+            "--moves" => moves = value()?.clone(),
+            "--theme" => options.theme = Theme::from_argument(value()?)?,
+            "--square-size" => {
+                let size = value()?;
+                options.square_size =
+                    size.parse().ok().filter(|&size| size > 0).ok_or_else(|| {
+                        format!("Invalid square size \"{size}\".\n{EXPORT_USAGE}")
+                    })?;
+            }
+            "--no-highlights" => options.highlights = false,
+            "--no-ownership" => options.ownership = false,
+            "--no-coordinates" => options.coordinates = false,
+            "--move-numbers" => options.move_numbers = true,
+            _ if output.is_none() && !argument.starts_with("--") => output = Some(argument),
+            _ => {
+                return Err(format!(
+                    "Unexpected argument \"{argument}\".\n{EXPORT_USAGE}"
+                ))
+            }
+        }
+    }
+    let output = output.ok_or_else(|| String::from(EXPORT_USAGE))?;
+
+    let mut game_grid = Grid::new(column_count, row_count);
+    let mut history = MoveHistory::new();
+    for notation in moves.split_whitespace() {
+        let wall: Wall = notation.parse()?;
+        if !game_grid.is_valid_wall(&wall) {
+            return Err(format!("Wall {wall} is outside the grid."));
+        }
+        game_grid.set_wall_with_wall(&wall, true);
+        history.record(&wall, true);
+    }
+
+    export(output, &game_grid, &history, &options)?;
+    println!("Exported {output}.");
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+pub fn export_window(
+    game_grid: &Grid,
+    history: &MoveHistory,
+    theme: &Theme,
+    optimal_moves: &HashSet<Wall>,
+) {
+    // Export the current window contents from the GUI shortcut, highlighting the moves the window
+    // shows, to the first numbered pair of files that would not overwrite an earlier export.
+    let mut options = ExportOptions::new(theme.clone());
+    options.optimal_moves = Some(optimal_moves.clone());
+    let number = (1..)
+        .find(|number| {
+            ["svg", "png"].iter().all(|extension| {
+                !Path::new(&format!("dots_and_boxes-{number}.{extension}")).exists()
+            })
+        })
+        .unwrap();
+    for extension in ["svg", "png"] {
+        let path = format!("dots_and_boxes-{number}.{extension}");
+        match export(&path, game_grid, history, &options) {
+            Ok(()) => println!("Exported {path}."),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::grid::Grid;
+use crate::history::MoveHistory;
+use crate::wall::Wall;
+
+// A game between two players (0 moves first) with the rules enforced: walls can only be set once,
+// on the player's own turn, and completing a box earns another turn.
+#[derive(Clone)]
+pub struct Game {
+    grid: Grid,
+    history: MoveHistory,
+    boxes: [i32; 2],
+    player: usize,
+    resigned: Option<usize>,
+}
+
+impl Game {
+    pub fn new(column_count: i32, row_count: i32) -> Self {
+        Self {
+            grid: Grid::new(column_count, row_count),
+            history: MoveHistory::new(),
+            boxes: [0, 0],
+            player: 0,
+            resigned: None,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn history(&self) -> &MoveHistory {
+        &self.history
+    }
+
+    pub fn boxes(&self) -> [i32; 2] {
+        self.boxes
+    }
+
+    // The player to move.
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn resigned(&self) -> Option<usize> {
+        self.resigned
+    }
+
+    pub fn is_over(&self) -> bool {
+        let box_total = (self.grid.column_count() - 1) * (self.grid.row_count() - 1);
+        self.resigned.is_some() || self.boxes[0] + self.boxes[1] == box_total
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        // The winning player once the game is over, or None for a draw or a game in progress.
+        if !self.is_over() {
+            return None;
+        }
+        match self.resigned {
+            Some(player) => Some(1 - player),
+            None if self.boxes[0] > self.boxes[1] => Some(0),
+            None if self.boxes[1] > self.boxes[0] => Some(1),
+            None => None,
+        }
+    }
+
+    pub fn play(&mut self, player: usize, wall: &Wall) -> Result<i32, String> {
+        // Set a wall for the player and return the number of boxes it completed.
+        if self.is_over() {
+            return Err(String::from("The game is over."));
+        }
+        if player != self.player {
+            return Err(String::from("It is not your turn."));
+        }
+        if !self.grid.is_valid_wall(wall) {
+            return Err(format!("Wall {wall} is outside the grid."));
+        }
+        if self
+            .grid
+            .get_wall(wall.is_column, wall.column, wall.row)
+            .set
+        {
+            return Err(format!("Wall {wall} is already set."));
+        }
+        let outcome = self.grid.make_move(wall);
+        self.history.record(wall, true);
+        self.boxes[player] += outcome.completed;
+        if !outcome.moves_again {
+            self.player = 1 - player;
+        }
+        Ok(outcome.completed)
+    }
+
+    pub fn resign(&mut self, player: usize) {
+        if !self.is_over() {
+            self.resigned = Some(player);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(notation: &str) -> Wall {
+        notation.parse().unwrap()
+    }
+
+    #[test]
+    fn players_alternate_until_a_box_is_completed() {
+        let mut game = Game::new(3, 3);
+        assert_eq!(game.play(0, &wall("a1h")), Ok(0));
+        assert_eq!(game.play(1, &wall("a1v")), Ok(0));
+        assert_eq!(game.play(0, &wall("b1v")), Ok(0));
+        assert_eq!(game.play(1, &wall("a2h")), Ok(1));
+        assert_eq!(game.player(), 1);
+        assert_eq!(game.boxes(), [0, 1]);
+    }
+
+    #[test]
+    fn rejects_moves_out_of_turn_and_repeated_walls() {
+        let mut game = Game::new(3, 3);
+        assert!(game.play(1, &wall("a1h")).is_err());
+        game.play(0, &wall("a1h")).unwrap();
+        assert!(game.play(1, &wall("a1h")).is_err());
+        assert!(game.play(1, &wall("c1h")).is_err());
+        assert_eq!(game.player(), 1);
+    }
+
+    #[test]
+    fn resigning_ends_the_game() {
+        let mut game = Game::new(3, 3);
+        game.resign(0);
+        assert!(game.is_over());
+        assert_eq!(game.winner(), Some(1));
+        assert!(game.play(0, &wall("a1h")).is_err());
+    }
+}
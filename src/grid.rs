@@ -1,8 +1,102 @@
+use crate::book;
+use crate::classify::{self, MoveClass};
+use crate::control::{self, ControlledValue};
+use crate::countdown::{self, Countdown};
+use crate::history::Owners;
+use crate::parity;
 use crate::square_walls::SquareWalls;
+use crate::tablebase;
 use crate::wall::Wall;
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
+// The advisor traces its search on standard error when DOTS_AND_BOXES_TRACE is set. Standard
+// output is left alone so it stays free for the engine protocol.
+fn tracing() -> bool {
+    static TRACING: OnceLock<bool> = OnceLock::new();
+    *TRACING.get_or_init(|| env::var_os("DOTS_AND_BOXES_TRACE").is_some())
+}
+
+macro_rules! trace {
+    ($($argument:tt)*) => {
+        if tracing() {
+            eprintln!($($argument)*);
+        }
+    };
+}
+
+macro_rules! trace_part {
+    ($($argument:tt)*) => {
+        if tracing() {
+            eprint!($($argument)*);
+        }
+    };
+}
+
+// Grid sizes in dots: columns are lettered from a to z in the notation and rows are numbered with
+// at most two digits.
+pub const MAXIMUM_COLUMNS: i32 = 26;
+pub const MAXIMUM_ROWS: i32 = 99;
+
+pub fn check_size(column_count: i32, row_count: i32) -> Result<(), String> {
+    if (2..=MAXIMUM_COLUMNS).contains(&column_count) && (2..=MAXIMUM_ROWS).contains(&row_count) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Grid size {column_count}x{row_count} is outside 2x2 to {MAXIMUM_COLUMNS}x{MAXIMUM_ROWS} dots."
+        ))
+    }
+}
+
+// The advisor's tiers, tried in order until one has moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tier {
+    // Complete a square that has three walls.
+    Capture,
+    // Set a wall that does not give the opponent a third wall on any square.
+    Safe,
+    // Open the shortest chain.
+    Sacrifice,
+}
+
+pub struct Analysis {
+    // The tier that produced the moves, or None when every wall is set.
+    pub tier: Option<Tier>,
+    pub moves: HashSet<Wall>,
+    // For the sacrifice tier, the length of the chain each candidate wall would open.
+    pub chain_lengths: HashMap<Wall, i32>,
+    // For the safe moves in an opening book, the share of games the mover went on to win (a draw
+    // counts as half a win).
+    pub win_rates: HashMap<Wall, f64>,
+    // For the safe tier, when there was time for playouts, the share of them after each move
+    // ending with the long chain parity the mover wants.
+    pub parity_rates: HashMap<Wall, f64>,
+    // The tier's moves from best to worst, starting with the moves above.
+    pub ranking: Vec<Wall>,
+}
+
+// What a move did: the boxes it completed (0, 1 or 2) and whether the player moves again, which
+// they do after completing a box.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveOutcome {
+    pub completed: i32,
+    pub moves_again: bool,
+}
+
+// Serialized as the grid size, the wall bitmaps and each square's wall count:
+//
+//     {"column_count":3,"row_count":2,"columns":[[true],[false],[false]],"rows":[[true,false],
+//      [false,false]],"wall_count":[[2],[0]]}
+//
+// The bitmaps are indexed by column then row like get_wall. When deserializing the wall counts may
+// be left out, in which case they are recalculated, but counts that are given must match the walls.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "GridData"))]
 pub struct Grid {
     column_count: i32,
     row_count: i32,
@@ -11,8 +105,55 @@ pub struct Grid {
     wall_count: Vec<Vec<i32>>,
 }
 
+// A grid as written, checked before it becomes a Grid.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct GridData {
+    column_count: i32,
+    row_count: i32,
+    columns: Vec<Vec<bool>>,
+    rows: Vec<Vec<bool>>,
+    #[serde(default)]
+    wall_count: Option<Vec<Vec<i32>>>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GridData> for Grid {
+    type Error = String;
+
+    fn try_from(data: GridData) -> Result<Self, Self::Error> {
+        // Check the size and the walls against it before allocating the grid.
+        check_size(data.column_count, data.row_count)?;
+        let has_shape = |bitmap: &[Vec<bool>], columns: i32, rows: i32| {
+            bitmap.len() == columns as usize
+                && bitmap.iter().all(|column| column.len() == rows as usize)
+        };
+        if !has_shape(&data.columns, data.column_count, data.row_count - 1) {
+            return Err(String::from("The column walls do not match the grid size."));
+        }
+        if !has_shape(&data.rows, data.column_count - 1, data.row_count) {
+            return Err(String::from("The row walls do not match the grid size."));
+        }
+        let mut new_grid = Grid::new(data.column_count, data.row_count);
+        new_grid.columns = data.columns;
+        new_grid.rows = data.rows;
+        new_grid.recalculate_wall_count();
+        if let Some(wall_count) = data.wall_count {
+            if wall_count != new_grid.wall_count {
+                return Err(String::from(
+                    "The wall counts do not match the walls of the squares.",
+                ));
+            }
+        }
+        Ok(new_grid)
+    }
+}
+
 impl Grid {
     pub fn new(column_count: i32, row_count: i32) -> Self {
+        if let Err(error) = check_size(column_count, row_count) {
+            panic!("{error}");
+        }
         let mut new_grid = Self {
             column_count,
             row_count,
@@ -21,7 +162,7 @@ impl Grid {
             wall_count: vec![vec![0; (row_count - 1) as usize]; (column_count - 1) as usize],
         };
         new_grid.recalculate_wall_count();
-        return new_grid;
+        new_grid
     }
 
     pub fn column_count(&self) -> i32 {
@@ -64,6 +205,16 @@ impl Grid {
             }
         }
 
+        // Setting a wall to its current state does not change any wall counts.
+        let current = if is_column {
+            self.columns[column as usize][row as usize]
+        } else {
+            self.rows[column as usize][row as usize]
+        };
+        if current == set {
+            return;
+        }
+
         // Update wall data.
         let wall_count_increment = if set { 1 } else { -1 };
         if is_column {
@@ -91,6 +242,83 @@ impl Grid {
         self.set_wall(wall.is_column, wall.column, wall.row, set);
     }
 
+    pub fn legal_moves(&self) -> impl Iterator<Item = Wall> + '_ {
+        // Every unset wall, by column then row with the row wall before the column wall.
+        (0..self.column_count)
+            .flat_map(move |column| {
+                (0..self.row_count).flat_map(move |row| {
+                    [false, true].map(|is_column| Wall {
+                        set: false,
+                        is_column,
+                        column,
+                        row,
+                    })
+                })
+            })
+            .filter(|wall| {
+                self.is_valid_wall(wall)
+                    && !self.get_wall(wall.is_column, wall.column, wall.row).set
+            })
+    }
+
+    pub fn make_move(&mut self, wall: &Wall) -> MoveOutcome {
+        // Set an unset wall and report the boxes it completed. Undo it with unmake_move.
+        if self.get_wall(wall.is_column, wall.column, wall.row).set {
+            panic!("Wall {} is already set.", wall);
+        }
+        self.set_wall_with_wall(wall, true);
+        let completed = self
+            .adjacent_squares(wall)
+            .into_iter()
+            .filter(|&(column, row)| self.get_wall_count(column, row) == 4)
+            .count() as i32;
+        MoveOutcome {
+            completed,
+            moves_again: completed > 0,
+        }
+    }
+
+    pub fn unmake_move(&mut self, wall: &Wall) {
+        // Clear a wall set by make_move, restoring the wall counts it changed.
+        if !self.get_wall(wall.is_column, wall.column, wall.row).set {
+            panic!("Wall {} is not set.", wall);
+        }
+        self.set_wall_with_wall(wall, false);
+    }
+
+    pub fn is_valid_wall(&self, wall: &Wall) -> bool {
+        // Column walls do not exist in the last row and row walls do not exist in the last column.
+        if wall.is_column {
+            wall.column >= 0
+                && wall.column < self.column_count
+                && wall.row >= 0
+                && wall.row < self.row_count - 1
+        } else {
+            wall.column >= 0
+                && wall.column < self.column_count - 1
+                && wall.row >= 0
+                && wall.row < self.row_count
+        }
+    }
+
+    pub fn adjacent_squares(&self, wall: &Wall) -> Vec<(i32, i32)> {
+        // The squares (one or two) that the wall is a side of.
+        let squares = if wall.is_column {
+            [(wall.column - 1, wall.row), (wall.column, wall.row)]
+        } else {
+            [(wall.column, wall.row - 1), (wall.column, wall.row)]
+        };
+        squares
+            .into_iter()
+            .filter(|&(column, row)| {
+                column >= 0
+                    && column < self.column_count - 1
+                    && row >= 0
+                    && row < self.row_count - 1
+            })
+            .collect()
+    }
+
     pub fn get_wall(&self, is_column: bool, column: i32, row: i32) -> Wall {
         // Check indices.
         if column < 0 {
@@ -137,28 +365,11 @@ impl Grid {
     }
 
     pub fn recalculate_wall_count(&mut self) {
-        for column in 0..self.column_count {
-            for row in 0..self.row_count {
-                if row < self.row_count - 1 && self.get_wall(true, column, row).set {
-                    if row < self.row_count - 1 && self.get_wall(true, column, row).set {
-                        // Not the last row and the column wall is set.
-                        if column < self.column_count - 1 {
-                            self.wall_count[column as usize][row as usize] += 1;
-                        } // Right square.
-                        if column > 0 {
-                            self.wall_count[(column - 1) as usize][row as usize];
-                        } // Left square.
-                    }
-                    if column < self.column_count - 1 && self.get_wall(false, column, row).set {
-                        // Not the last column and the row wall is set.
-                        if row < self.row_count - 1 {
-                            self.wall_count[column as usize][row as usize] += 1;
-                        } // Below square.
-                        if row > 0 {
-                            self.wall_count[column as usize][(row - 1) as usize] += 1;
-                        } // Above square.
-                    }
-                }
+        // Derive every square's wall count from the wall data, discarding the previous counts.
+        for column in 0..(self.column_count - 1) {
+            for row in 0..(self.row_count - 1) {
+                self.wall_count[column as usize][row as usize] =
+                    self.get_square_walls(column, row).get_walls(true).len() as i32;
             }
         }
     }
@@ -233,54 +444,188 @@ impl Grid {
     }
 
     pub fn get_optimal_moves(&self) -> HashSet<Wall> {
+        self.analyze().moves
+    }
+
+    pub fn ranked_moves(&self, limit: Duration) -> Vec<Wall> {
+        self.analyze_ranked(limit).ranking
+    }
+
+    pub fn classify_moves(&self) -> HashMap<Wall, MoveClass> {
+        // Each legal wall as a capture, double-cross, safe move, sacrifice or loony move.
+        classify::classify_moves(self)
+    }
+
+    pub fn countdown(&self) -> Countdown {
+        // The most safe moves left and who runs out of them first.
+        countdown::countdown(self)
+    }
+
+    pub fn controlled_value(&self) -> Option<ControlledValue> {
+        // The controlled values of a loony endgame and the margin of the player in control.
+        control::controlled_value(self)
+    }
+
+    pub fn tablebase_score(&self) -> Option<i32> {
+        // The score of the rest of the game for the player to move, if an installed tablebase
+        // covers the grid.
+        tablebase::find(self.column_count, self.row_count)?.score(self)
+    }
+
+    pub fn analyze(&self) -> Analysis {
+        // The advisor without the long chain parity playouts, which would make it slow on large
+        // grids.
+        self.analyze_within(Duration::ZERO)
+    }
+
+    pub fn analyze_ranked(&self, limit: Duration) -> Analysis {
+        // The advisor, with up to the given time for long chain parity playouts.
+        self.analyze_within(limit)
+    }
+
+    fn analyze_within(&self, playout_time: Duration) -> Analysis {
+        // Perfect play from an installed tablebase when one covers the grid, given the tier of the
+        // heuristics that would have found the moves, otherwise the heuristics with safe moves
+        // narrowed to an opening book's moves with the best win rate and margin, or failing that to
+        // the moves most likely to keep the long chain parity in the playouts that fit in the time
+        // given (no time for any leaves the safe moves as they are).
+        // A time too long to add to the clock leaves the playouts without a deadline.
+        let deadline = Instant::now().checked_add(playout_time);
+        let mut analysis = self.analyze_heuristics();
+        let Some(tablebase) = tablebase::find(self.column_count, self.row_count) else {
+            if analysis.tier == Some(Tier::Safe) {
+                let book = book::find(self.column_count, self.row_count);
+                let book_moves = book.map_or(HashMap::new(), |book| book.moves(self));
+                let win_rates: HashMap<Wall, f64> = analysis
+                    .moves
+                    .iter()
+                    .filter_map(|wall| Some((*wall, book_moves.get(wall)?.win_rate())))
+                    .collect();
+                let rates = if win_rates.is_empty() {
+                    if !playout_time.is_zero() {
+                        analysis.parity_rates =
+                            parity::parity_rates(self, &analysis.moves, deadline);
+                    }
+                    if analysis.parity_rates.is_empty() {
+                        return analysis;
+                    }
+                    &analysis.parity_rates
+                } else {
+                    &win_rates
+                };
+                // Book moves with the same win rate are told apart by their average margin.
+                let margin = |wall: &Wall| {
+                    book_moves
+                        .get(wall)
+                        .map_or(0.0, |stats| stats.average_margin())
+                };
+                let best = rates
+                    .keys()
+                    .map(|wall| (rates[wall], margin(wall)))
+                    .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+                    .unwrap_or((0.0, 0.0));
+                trace!("Narrowing the safe moves to a rate and margin of {best:?}.");
+                analysis.moves = rates
+                    .iter()
+                    .filter(|&(wall, &rate)| (rate, margin(wall)) == best)
+                    .map(|(wall, _)| *wall)
+                    .collect();
+                // Moves without a rate (outside the book) rank last.
+                let rate = |wall: &Wall| rates.get(wall).copied().unwrap_or(-1.0);
+                analysis.ranking.sort_by(|a, b| {
+                    rate(b)
+                        .total_cmp(&rate(a))
+                        .then(margin(b).total_cmp(&margin(a)))
+                        .then(wall_order(a).cmp(&wall_order(b)))
+                });
+                analysis.win_rates = win_rates;
+            }
+            return analysis;
+        };
+        let moves = tablebase.best_moves(self);
+        trace!("Playing from the tablebase: {} moves.", moves.len());
+        analysis.tier = if moves.is_empty() {
+            None
+        } else if moves
+            .iter()
+            .any(|wall| self.clone().make_move(wall).completed > 0)
+        {
+            Some(Tier::Capture)
+        } else if analysis.tier == Some(Tier::Safe)
+            && moves.iter().any(|wall| analysis.moves.contains(wall))
+        {
+            Some(Tier::Safe)
+        } else {
+            Some(Tier::Sacrifice)
+        };
+        analysis
+            .chain_lengths
+            .retain(|wall, _| moves.contains(wall));
+        analysis.moves = moves;
+        promote(&mut analysis.ranking, &analysis.moves);
+        analysis
+    }
+
+    pub fn analyze_heuristics(&self) -> Analysis {
+        // The advisor's heuristics alone, without tablebases or opening books.
         let mut optimal_moves = HashSet::new();
+        trace!("Analyzing:\n{self}");
 
         // Find unset walls on squares with three set walls.
-        println!("Searching for creatable boxes.");
+        trace!("Searching for creatable boxes.");
         for column in 0..(self.column_count - 1) {
             for row in 0..(self.row_count - 1) {
                 if self.get_wall_count(column, row) == 3 {
                     // The square has three walls. Fill in the last wall to complete the square.
-                    print!("    Coordinate: ({column}, {row}), Walls:");
+                    trace_part!("    Coordinate: ({column}, {row}), Walls:");
                     let square_walls = self.get_square_walls(column, row);
                     // Exactly one of the below conditions should evaluate to true.
                     if !square_walls.top.set {
-                        print!(" top");
+                        trace_part!(" top");
                         optimal_moves.insert(square_walls.top);
                     }
                     if !square_walls.right.set {
-                        print!(" right");
+                        trace_part!(" right");
                         optimal_moves.insert(square_walls.right);
                     }
                     if !square_walls.bottom.set {
-                        print!(" bottom");
+                        trace_part!(" bottom");
                         optimal_moves.insert(square_walls.bottom);
                     }
                     if !square_walls.left.set {
-                        print!(" left");
+                        trace_part!(" left");
                         optimal_moves.insert(square_walls.left);
                     }
-                    println!(".");
+                    trace!(".");
                 }
             }
         }
-        if optimal_moves.len() > 0 {
-            return optimal_moves;
+        if !optimal_moves.is_empty() {
+            let mut ranking: Vec<Wall> = optimal_moves.iter().copied().collect();
+            ranking.sort_by_key(wall_order);
+            return Analysis {
+                tier: Some(Tier::Capture),
+                moves: optimal_moves,
+                chain_lengths: HashMap::new(),
+                win_rates: HashMap::new(),
+                parity_rates: HashMap::new(),
+                ranking,
+            };
         }
 
         // Find walls with adjacent squares with less than two walls.
-        println!("Searching for safe moves.");
+        trace!("Searching for safe moves.");
         for column in 0..(self.column_count - 1) {
             for row in 0..(self.row_count - 1) {
                 if self.get_wall_count(column, row) < 2 {
                     // The square has less than two walls.
-                    print!("    Coordinate: ({column}, {row}), Walls:");
+                    trace_part!("    Coordinate: ({column}, {row}), Walls:");
                     let square_walls = self.get_square_walls(column, row);
                     if !square_walls.top.set
                         && (row == 0 || self.get_wall_count(column, row - 1) < 2)
                     {
                         // The top wall is not set and the above square (if one exists) has less than two walls.
-                        print!(" top");
+                        trace_part!(" top");
                         optimal_moves.insert(square_walls.top);
                     }
                     if !square_walls.right.set
@@ -288,29 +633,40 @@ impl Grid {
                         || self.get_wall_count(column + 1, row) < 2)
                     {
                         // The right wall is not set and the right square (if one exists) has less than two walls.
-                        print!(" right");
+                        trace_part!(" right");
                         optimal_moves.insert(square_walls.right);
                     }
                     if !square_walls.bottom.set
                         && (row == self.row_count - 2 || self.get_wall_count(column, row + 1) < 2)
                     {
                         // The bottom wall is not set and the below square (if one exists) has less than two walls.
-                        print!(" bottom");
+                        trace_part!(" bottom");
                         optimal_moves.insert(square_walls.bottom);
                     }
                     if !square_walls.left.set
                         && (column == 0 || self.get_wall_count(column - 1, row) < 2)
                     {
                         // The left wall is not set and the left square (if one exists) has less than two walls.
-                        print!(" left");
+                        trace_part!(" left");
                         optimal_moves.insert(square_walls.left);
                     }
-                    println!(".");
+                    trace!(".");
                 }
             }
         }
-        if optimal_moves.len() > 0 {
-            return optimal_moves;
+        if !optimal_moves.is_empty() {
+            // The safe moves are only ranked by the advisor, which plays out the rest of the safe
+            // phase.
+            let mut ranking: Vec<Wall> = optimal_moves.iter().copied().collect();
+            ranking.sort_by_key(wall_order);
+            return Analysis {
+                tier: Some(Tier::Safe),
+                moves: optimal_moves,
+                chain_lengths: HashMap::new(),
+                win_rates: HashMap::new(),
+                parity_rates: HashMap::new(),
+                ranking,
+            };
         }
 
         // Find walls that each trigger a shortest chain (multiple chains may have the least length).
@@ -319,7 +675,7 @@ impl Grid {
         // other end to form a perfect loop) walls in its "tail" will result in a shorter chain.
 
         fn get_chain_length(temporary_grid: &mut Grid, mut column: i32, mut row: i32) -> i32 {
-            println!("    Evaluating chain ({column}, {row}):");
+            trace!("    Evaluating chain ({column}, {row}):");
             let mut chain_length = 0;
 
             // While the current position is in squares and the current square has three walls.
@@ -329,28 +685,30 @@ impl Grid {
                 && row < temporary_grid.row_count - 1
                 && temporary_grid.get_wall_count(column, row) == 3
             {
-                print!("        Coordinate: ({column}, {row}), Length: {chain_length}. Setting ");
+                trace_part!(
+                    "        Coordinate: ({column}, {row}), Length: {chain_length}. Setting "
+                );
 
                 // Set the unset wall and move in that direction to the next square.
                 let square_walls = temporary_grid.get_square_walls(column, row);
                 if !square_walls.top.set {
-                    println!("top");
+                    trace!("top");
                     temporary_grid.set_wall_with_wall(&square_walls.top, true);
                     row -= 1;
                 } else if !square_walls.right.set {
-                    println!("right");
+                    trace!("right");
                     temporary_grid.set_wall_with_wall(&square_walls.right, true);
                     column += 1;
                 } else if !square_walls.bottom.set {
-                    println!("bottom");
+                    trace!("bottom");
                     temporary_grid.set_wall_with_wall(&square_walls.bottom, true);
                     row += 1;
                 } else if !square_walls.left.set {
-                    println!("left");
+                    trace!("left");
                     temporary_grid.set_wall_with_wall(&square_walls.left, true);
                     column -= 1;
                 } else {
-                    println!("none");
+                    trace!("none");
                     break;
                 }
 
@@ -368,12 +726,12 @@ impl Grid {
                 }
             }
 
-            println!("        Chain length: {chain_length}.");
+            trace!("        Chain length: {chain_length}.");
             chain_length
         }
 
         // Get the length of each chain triggered by a wall.
-        println!("Searching for the shortest chains.");
+        trace!("Searching for the shortest chains.");
         let mut chain_lengths: HashMap<Wall, i32> = HashMap::new();
         for column in 0..(self.column_count - 1) {
             for row in 0..(self.row_count - 1) {
@@ -432,8 +790,8 @@ impl Grid {
 
         // Get the walls that each trigger the shortest chain.
         let minimum_length = *chain_lengths.values().min().unwrap_or(&0);
-        for (wall, length) in chain_lengths {
-            println!(
+        for (&wall, &length) in &chain_lengths {
+            trace!(
                 "    {} ({}, {}) generates a chain with length {length}.",
                 if wall.is_column { "Column" } else { "Row" },
                 wall.column,
@@ -444,6 +802,467 @@ impl Grid {
             }
         }
 
-        optimal_moves
+        // Rank every sacrifice by the length of the chain it opens.
+        let mut ranking: Vec<Wall> = chain_lengths.keys().copied().collect();
+        ranking.sort_by_key(|wall| (chain_lengths[wall], wall_order(wall)));
+        Analysis {
+            tier: if optimal_moves.is_empty() {
+                None
+            } else {
+                Some(Tier::Sacrifice)
+            },
+            moves: optimal_moves,
+            chain_lengths,
+            win_rates: HashMap::new(),
+            parity_rates: HashMap::new(),
+            ranking,
+        }
+    }
+}
+
+fn wall_order(wall: &Wall) -> (i32, i32, bool) {
+    // A fixed order for walls that rank equally.
+    (wall.column, wall.row, wall.is_column)
+}
+
+fn promote(ranking: &mut Vec<Wall>, moves: &HashSet<Wall>) {
+    // Move the chosen moves to the front of a ranking, adding any it does not have.
+    ranking.sort_by_key(|wall| !moves.contains(wall));
+    let mut missing: Vec<Wall> = moves
+        .iter()
+        .filter(|wall| !ranking.contains(wall))
+        .copied()
+        .collect();
+    missing.sort_by_key(wall_order);
+    let ranked = ranking.iter().filter(|wall| moves.contains(wall)).count();
+    ranking.splice(ranked..ranked, missing);
+}
+
+// Grids are written as ASCII diagrams with a line of dots and row walls for each row of dots and a
+// line of column walls and squares between them. Completed squares may hold their owner's letter.
+//
+//     +--+--+
+//     |A |  |
+//     +--+  +
+const PLAYER_LETTERS: [char; 2] = ['A', 'B'];
+
+impl Grid {
+    pub fn diagram(&self, owners: Option<&Owners>) -> String {
+        let mut text = String::new();
+        for row in 0..self.row_count {
+            for column in 0..self.column_count {
+                text.push('+');
+                if column < self.column_count - 1 {
+                    // Row walls do not exist in the last column.
+                    text.push_str(if self.get_wall(false, column, row).set {
+                        "--"
+                    } else {
+                        "  "
+                    });
+                }
+            }
+            text.push('\n');
+            if row == self.row_count - 1 {
+                // Column walls and squares do not exist below the last row.
+                break;
+            }
+            for column in 0..self.column_count {
+                text.push(if self.get_wall(true, column, row).set {
+                    '|'
+                } else {
+                    ' '
+                });
+                if column < self.column_count - 1 {
+                    let owner = owners.and_then(|owners| owners[column as usize][row as usize]);
+                    text.push(owner.map_or(' ', |player| PLAYER_LETTERS[player]));
+                    text.push(' ');
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagram(None))
+    }
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // Parse a diagram as written by Display. Blank lines before and after the diagram are
+        // ignored, as are owner letters (which must be in completed squares). Errors give the line
+        // and column (counting from 1) of the first character that does not fit.
+        let lines: Vec<(usize, &str)> = text.lines().enumerate().collect();
+        let first = lines.iter().position(|(_, line)| !line.trim().is_empty());
+        let last = lines.iter().rposition(|(_, line)| !line.trim().is_empty());
+        let lines = match (first, last) {
+            (Some(first), Some(last)) => &lines[first..=last],
+            _ => return Err(String::from("The diagram is empty.")),
+        };
+        let error = |index: usize, position: usize, message: &str| {
+            format!(
+                "Line {}, column {}: {message}",
+                lines[index].0 + 1,
+                position + 1
+            )
+        };
+
+        let width = lines[0].1.trim_end().chars().count();
+        if width < 4 || width % 3 != 1 {
+            return Err(error(
+                0,
+                width,
+                "expected a line of at least two dots three characters apart.",
+            ));
+        }
+        if lines.len() < 3 || lines.len() % 2 == 0 {
+            return Err(error(
+                lines.len() - 1,
+                0,
+                "expected alternating lines of dots and squares starting and ending with dots.",
+            ));
+        }
+        let column_count = (width / 3 + 1) as i32;
+        let row_count = (lines.len() / 2 + 1) as i32;
+        check_size(column_count, row_count)?;
+        let mut new_grid = Grid::new(column_count, row_count);
+        let mut letters = Vec::new();
+        for (index, (_, line)) in lines.iter().enumerate() {
+            let characters: Vec<char> = line.trim_end().chars().collect();
+            if characters.len() > width {
+                return Err(error(
+                    index,
+                    width,
+                    "unexpected character after the last dot.",
+                ));
+            }
+            // Lines may have had trailing spaces removed.
+            let at = |position: usize| characters.get(position).copied().unwrap_or(' ');
+            let row = (index / 2) as i32;
+            for column in 0..column_count {
+                let position = column as usize * 3;
+                let last = column == column_count - 1;
+                if index % 2 == 0 {
+                    if at(position) != '+' {
+                        return Err(error(index, position, "expected '+'."));
+                    }
+                    match (last, at(position + 1), at(position + 2)) {
+                        (true, _, _) | (false, ' ', ' ') => {}
+                        (false, '-', '-') => new_grid.set_wall(false, column, row, true),
+                        _ => {
+                            return Err(error(
+                                index,
+                                position + 1,
+                                "expected \"--\" or two spaces between dots.",
+                            ))
+                        }
+                    }
+                } else {
+                    match at(position) {
+                        '|' => new_grid.set_wall(true, column, row, true),
+                        ' ' => {}
+                        _ => return Err(error(index, position, "expected '|' or a space.")),
+                    }
+                    match (last, at(position + 1), at(position + 2)) {
+                        (true, _, _) | (false, ' ', ' ') => {}
+                        (false, letter, ' ') if PLAYER_LETTERS.contains(&letter) => {
+                            letters.push((index, position + 1, column, row));
+                        }
+                        _ => {
+                            return Err(error(
+                                index,
+                                position + 1,
+                                "expected two spaces or an owner letter and a space.",
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        for (index, position, column, row) in letters {
+            if new_grid.get_wall_count(column, row) != 4 {
+                return Err(error(
+                    index,
+                    position,
+                    "the square has an owner but is not complete.",
+                ));
+            }
+        }
+        Ok(new_grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wall::column_label;
+    use proptest::prelude::*;
+
+    // Fails through proptest so property failures are shrunk to a minimal case.
+    fn check_wall_count_consistent(grid: &Grid) -> Result<(), TestCaseError> {
+        for column in 0..(grid.column_count() - 1) {
+            for row in 0..(grid.row_count() - 1) {
+                let derived = grid.get_square_walls(column, row).get_walls(true).len() as i32;
+                prop_assert_eq!(
+                    grid.get_wall_count(column, row),
+                    derived,
+                    "Wall count of square ({}, {}) does not match its walls.",
+                    column,
+                    row
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Arguments of a set_wall call: (is_column, column, row, set).
+    type SetWall = (bool, i32, i32, bool);
+
+    // A grid size and a sequence of set_wall calls valid for that size.
+    fn grid_and_moves() -> impl Strategy<Value = (i32, i32, Vec<SetWall>)> {
+        (2..8i32, 2..8i32).prop_flat_map(|(column_count, row_count)| {
+            let set_wall = (any::<bool>(), 0..column_count, 0..row_count, any::<bool>()).prop_map(
+                move |(is_column, column, row, set)| {
+                    // Column walls do not exist in the last row and row walls do not exist in
+                    // the last column, so fold those positions back into the grid.
+                    if is_column {
+                        (is_column, column, row % (row_count - 1), set)
+                    } else {
+                        (is_column, column % (column_count - 1), row, set)
+                    }
+                },
+            );
+            (
+                Just(column_count),
+                Just(row_count),
+                prop::collection::vec(set_wall, 0..200),
+            )
+        })
+    }
+
+    #[test]
+    fn new_grid_has_no_walls() {
+        let grid = Grid::new(5, 4);
+        for column in 0..4 {
+            for row in 0..3 {
+                assert_eq!(grid.get_wall_count(column, row), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn grids_have_a_letter_for_every_column() {
+        assert!(check_size(26, 99).is_ok());
+        assert!(check_size(27, 2).is_err());
+        assert!(check_size(2, 100).is_err());
+        assert!(check_size(1, 2).is_err());
+        assert!(check_size(2, -3).is_err());
+        assert_eq!(column_label(25), 'z');
+        assert_eq!(column_label(200), '?');
+        let diagram = format!(
+            "{}+\n{}|\n{}+\n",
+            "+--".repeat(26),
+            "|  ".repeat(26),
+            "+--".repeat(26)
+        );
+        assert_eq!(
+            diagram.parse::<Grid>().err(),
+            Some(String::from("Grid size 27x2 is outside 2x2 to 26x99 dots."))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Grid size 27x2 is outside")]
+    fn new_rejects_grids_without_a_letter_for_every_column() {
+        Grid::new(27, 2);
+    }
+
+    #[test]
+    fn setting_every_wall_completes_every_square() {
+        let mut grid = Grid::new(4, 5);
+        for column in 0..4 {
+            for row in 0..5 {
+                if row < 4 {
+                    grid.set_wall(true, column, row, true);
+                }
+                if column < 3 {
+                    grid.set_wall(false, column, row, true);
+                }
+            }
+        }
+        for column in 0..3 {
+            for row in 0..4 {
+                assert_eq!(grid.get_wall_count(column, row), 4);
+            }
+        }
+        check_wall_count_consistent(&grid).unwrap();
+    }
+
+    #[test]
+    fn setting_a_wall_twice_counts_it_once() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_wall(true, 1, 0, true);
+        grid.set_wall(true, 1, 0, true);
+        assert_eq!(grid.get_wall_count(0, 0), 1);
+        assert_eq!(grid.get_wall_count(1, 0), 1);
+        grid.set_wall(true, 1, 0, false);
+        grid.set_wall(true, 1, 0, false);
+        assert_eq!(grid.get_wall_count(0, 0), 0);
+        assert_eq!(grid.get_wall_count(1, 0), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn set_wall_keeps_wall_count_consistent((column_count, row_count, moves) in grid_and_moves()) {
+            let mut grid = Grid::new(column_count, row_count);
+            for (is_column, column, row, set) in moves {
+                grid.set_wall(is_column, column, row, set);
+                check_wall_count_consistent(&grid)?;
+            }
+        }
+
+        #[test]
+        fn recalculate_wall_count_is_idempotent((column_count, row_count, moves) in grid_and_moves()) {
+            let mut grid = Grid::new(column_count, row_count);
+            for (is_column, column, row, set) in moves {
+                grid.set_wall(is_column, column, row, set);
+            }
+            let wall_count = grid.wall_count.clone();
+            grid.recalculate_wall_count();
+            prop_assert_eq!(&grid.wall_count, &wall_count);
+            check_wall_count_consistent(&grid)?;
+        }
+
+        #[test]
+        fn recalculate_wall_count_repairs_drift(
+            (column_count, row_count, moves) in grid_and_moves(),
+            drift in -4..5i32,
+        ) {
+            let mut grid = Grid::new(column_count, row_count);
+            for (is_column, column, row, set) in moves {
+                grid.set_wall(is_column, column, row, set);
+            }
+            for counts in grid.wall_count.iter_mut() {
+                for count in counts.iter_mut() {
+                    *count += drift;
+                }
+            }
+            grid.recalculate_wall_count();
+            check_wall_count_consistent(&grid)?;
+        }
+    }
+
+    #[test]
+    fn make_move_reports_captures_and_unmake_move_restores() {
+        let mut game_grid: Grid = "\
++--+--+
+|  |  |
++--+  +
+"
+        .parse()
+        .unwrap();
+        let before = game_grid.to_string();
+        assert_eq!(game_grid.legal_moves().count(), 1);
+        let wall = game_grid.legal_moves().next().unwrap();
+        assert_eq!(wall.to_string(), "b2h");
+        assert_eq!(
+            game_grid.make_move(&wall),
+            MoveOutcome {
+                completed: 1,
+                moves_again: true
+            }
+        );
+        assert_eq!(game_grid.legal_moves().count(), 0);
+        game_grid.unmake_move(&wall);
+        assert_eq!(game_grid.to_string(), before);
+        assert_eq!(game_grid.get_wall_count(1, 0), 3);
+    }
+
+    #[test]
+    fn diagrams_round_trip() {
+        let diagram = "\
++--+--+
+|A |  |
++--+  +
+";
+        let game_grid: Grid = diagram.parse().unwrap();
+        assert_eq!(game_grid.get_wall_count(0, 0), 4);
+        assert_eq!(game_grid.get_wall_count(1, 0), 3);
+        let mut owners = vec![vec![None]; 2];
+        owners[0][0] = Some(0);
+        assert_eq!(game_grid.diagram(Some(&owners)), diagram);
+        assert_eq!(game_grid.to_string(), diagram.replace('A', " "));
+    }
+
+    #[test]
+    fn diagram_errors_give_the_line_and_column() {
+        assert_eq!(
+            "\n+--+\n|  x\n+--+".parse::<Grid>().err(),
+            Some(String::from("Line 3, column 4: expected '|' or a space."))
+        );
+        assert_eq!(
+            "+--+\n|B  \n+  +".parse::<Grid>().err(),
+            Some(String::from(
+                "Line 2, column 2: the square has an owner but is not complete."
+            ))
+        );
+        assert_eq!(
+            "+--+\n|  |".parse::<Grid>().err(),
+            Some(String::from(
+                "Line 2, column 1: expected alternating lines of dots and squares starting and \
+ending with dots."
+            ))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn grids_round_trip_through_json() {
+        let mut grid = Grid::new(3, 2);
+        grid.set_wall(true, 0, 0, true);
+        grid.set_wall(false, 0, 0, true);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(
+            json,
+            r#"{"column_count":3,"row_count":2,"columns":[[true],[false],[false]],"rows":[[true,false],[false,false]],"wall_count":[[2],[0]]}"#
+        );
+        let read: Grid = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.columns, grid.columns);
+        assert_eq!(read.rows, grid.rows);
+        assert_eq!(read.wall_count, grid.wall_count);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_checks_or_recalculates_wall_counts() {
+        let walls = r#""column_count":3,"row_count":2,"columns":[[true],[false],[false]],"rows":[[true,false],[false,false]]"#;
+        let read: Grid = serde_json::from_str(&format!("{{{walls}}}")).unwrap();
+        assert_eq!(read.get_wall_count(0, 0), 2);
+        assert!(
+            serde_json::from_str::<Grid>(&format!(r#"{{{walls},"wall_count":[[1],[0]]}}"#))
+                .is_err()
+        );
+        assert!(serde_json::from_str::<Grid>(
+            r#"{"column_count":3,"row_count":2,"columns":[[true]],"rows":[[true,false],[false,false]]}"#
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_checks_the_size_and_shape_first() {
+        let read = serde_json::from_str::<Grid>(
+            r#"{"column_count":3000000,"row_count":3000000,"columns":[],"rows":[]}"#,
+        );
+        assert!(read.is_err_and(|error| error.to_string().contains("outside 2x2")));
+        let read = serde_json::from_str::<Grid>(
+            r#"{"column_count":3,"row_count":2,"columns":[[true],[false],[false]],"rows":[[true],[false,false]]}"#,
+        );
+        assert!(read.is_err_and(|error| error.to_string().contains("row walls")));
     }
 }
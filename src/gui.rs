@@ -0,0 +1,976 @@
+use flo_canvas::*;
+use flo_draw::binding::BindRef;
+use flo_draw::*;
+
+use crate::analysis::{Analyst, Report};
+use crate::board::{
+    draw_board, draw_coordinates, draw_cursor, draw_heatmap, draw_move_classes, draw_square,
+    draw_walls, COORDINATE_MARGIN, PLAYER_LETTERS, STATUS_LAYER, WALL_LAYER,
+};
+use crate::cursor::Cursor;
+use crate::engine::{EngineMove, ExternalEngine};
+use crate::export;
+use crate::grid::Grid;
+use crate::history::{MoveHistory, Owners};
+use crate::observer::{open_stream, read_events, GameEvent};
+use crate::painter::Painter;
+use crate::solver::default_threads;
+use crate::theme::Theme;
+use crate::wall::Wall;
+
+use futures::channel::mpsc;
+use futures::executor;
+use futures::prelude::*;
+use futures::stream;
+
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+
+// Leave room on each side of the grid for the coordinate labels.
+const MARGIN: f32 = 2.0 * COORDINATE_MARGIN - 1.0;
+
+fn calculate_transform(
+    width: u64,
+    height: u64,
+    columns: i32,
+    rows: i32,
+) -> (Transform2D, Transform2D) {
+    let width = width as f32;
+    let height = height as f32;
+    let columns = columns as f32;
+    let rows = rows as f32;
+
+    let window_aspect_ratio = width / height;
+    let canvas_transform = if window_aspect_ratio > (columns + MARGIN) / (rows + MARGIN) {
+        // Window aspect ratio (width:height) is greater than grid aspect ratio
+        // (columns:rows) therefore the grid size is bounded by height.
+
+        // Scale to fit grid and invert y-axis (to better match original
+        // implementation) then translate to center grid within window.
+        Transform2D::scale(2.0 / (rows + MARGIN), -2.0 / (rows + MARGIN))
+            * Transform2D::translate(columns / -2.0 + 0.5, rows / -2.0 + 0.5)
+    } else {
+        // Window aspect ratio (width:height) is less than or equal to grid aspect ratio
+        // (columns:rows) therefore the grid size is bounded by width.
+
+        // Scale to fit grid and invert y-axis (to better match original
+        // implementation) then translate to center grid within window.
+        Transform2D::scale(
+            2.0 * window_aspect_ratio / (columns + MARGIN),
+            -2.0 * window_aspect_ratio / (columns + MARGIN),
+        ) * Transform2D::translate(columns / -2.0 + 0.5, rows / -2.0 + 0.5)
+    };
+    let window_transform = canvas_transform.invert().unwrap()
+        * Transform2D::translate(-window_aspect_ratio, 1.0)
+        * Transform2D::scale(2.0 / height, -2.0 / height);
+    (canvas_transform, window_transform)
+}
+
+// The report is None while the analysis thread is still working on the position.
+#[allow(clippy::too_many_arguments)]
+fn redraw_all(
+    graphics_context: &mut Vec<Draw>,
+    transform: Transform2D,
+    game_grid: &Grid,
+    history: &MoveHistory,
+    report: Option<&Report>,
+    cursor: &Cursor,
+    theme: &Theme,
+    show_coordinates: bool,
+    marks: Marks,
+) {
+    graphics_context.clear_canvas(theme.background_fill);
+
+    graphics_context.identity_transform();
+    graphics_context.transform(transform);
+
+    let no_moves = HashSet::new();
+    draw_board(
+        graphics_context,
+        game_grid,
+        report.map_or(&no_moves, |report| &report.optimal_moves),
+        None,
+        theme,
+    );
+    draw_status(graphics_context, game_grid, history, report, theme);
+    draw_move_marks(graphics_context, game_grid, report, marks, theme);
+    draw_cursor(graphics_context, cursor, theme);
+    if show_coordinates {
+        draw_coordinates(graphics_context, game_grid, theme);
+    }
+}
+
+fn redraw_lines(
+    graphics_context: &mut Vec<Draw>,
+    game_grid: &Grid,
+    history: &MoveHistory,
+    report: Option<&Report>,
+    marks: Marks,
+    theme: &Theme,
+) {
+    Painter::clear_layer(graphics_context, WALL_LAYER);
+
+    let no_moves = HashSet::new();
+    draw_walls(
+        graphics_context,
+        game_grid,
+        report.map_or(&no_moves, |report| &report.optimal_moves),
+        theme,
+    );
+    draw_status(graphics_context, game_grid, history, report, theme);
+    draw_move_marks(graphics_context, game_grid, report, marks, theme);
+}
+
+// Which marks are drawn on the unset walls; the heatmap hides the move classes while it is on.
+#[derive(Clone, Copy)]
+struct Marks {
+    move_classes: bool,
+    heatmap: bool,
+}
+
+fn draw_move_marks(
+    graphics_context: &mut Vec<Draw>,
+    game_grid: &Grid,
+    report: Option<&Report>,
+    marks: Marks,
+    theme: &Theme,
+) {
+    // Once the analysis has finished, mark each unset wall with how many boxes it loses against
+    // the best move or, with the heatmap off, what the move does.
+    let Some(report) = report else {
+        return;
+    };
+    if marks.heatmap {
+        draw_heatmap(graphics_context, game_grid, &report.score_losses, theme);
+    } else if marks.move_classes {
+        draw_move_classes(graphics_context, game_grid, &report.move_classes, theme);
+    }
+}
+
+fn draw_status(
+    graphics_context: &mut Vec<Draw>,
+    game_grid: &Grid,
+    history: &MoveHistory,
+    report: Option<&Report>,
+    theme: &Theme,
+) {
+    // Below the grid, the safe move countdown or, while the analysis has not finished (so the
+    // highlights are missing), that the advisor is thinking.
+    Painter::clear_layer(graphics_context, STATUS_LAYER);
+    let status = match report {
+        Some(report) => {
+            let player = history.next_player(game_grid.column_count(), game_grid.row_count());
+            let opener = if report.countdown.mover_opens {
+                player
+            } else {
+                1 - player
+            };
+            format!(
+                "{} safe moves left, {} opens the first chain.",
+                report.countdown.safe_moves, PLAYER_LETTERS[opener]
+            )
+        }
+        None => String::from("Thinking..."),
+    };
+    graphics_context.text(
+        STATUS_LAYER,
+        (
+            (game_grid.column_count() - 1) as f32 / 2.0,
+            (game_grid.row_count() - 1) as f32 + 0.5,
+        ),
+        0.25,
+        &status,
+        theme.dot_fill,
+    );
+}
+
+fn line_clicked(
+    graphics_context: &mut Vec<Draw>,
+    game_grid: &mut Grid,
+    history: &mut MoveHistory,
+    is_column: bool,
+    row: i32,
+    column: i32,
+    theme: &Theme,
+) -> bool {
+    // Toggle the wall and return whether it was inside the grid. The advisor's highlights are
+    // cleared until the new position has been analysed.
+    if is_column {
+        if row < 0
+            || row >= game_grid.row_count() - 1
+            || column < 0
+            || column >= game_grid.column_count()
+        {
+            return false;
+        } // Click out of bounds.
+
+        // Toggle a column.
+        let wall = game_grid.get_wall(true, column, row);
+        history.toggle(game_grid, &wall);
+
+        // Update squares.
+        if column > 0 {
+            draw_square(
+                graphics_context,
+                (column - 1) as f32,
+                row as f32,
+                game_grid.get_wall_count(column - 1, row),
+                None,
+                theme,
+            );
+        }
+        if column < game_grid.column_count() - 1 {
+            draw_square(
+                graphics_context,
+                column as f32,
+                row as f32,
+                game_grid.get_wall_count(column, row),
+                None,
+                theme,
+            );
+        }
+    } else {
+        if row < 0
+            || row >= game_grid.row_count()
+            || column < 0
+            || column >= game_grid.column_count() - 1
+        {
+            return false;
+        } // Click out of bounds.
+
+        // Toggle a row.
+        let wall = game_grid.get_wall(false, column, row);
+        history.toggle(game_grid, &wall);
+
+        // Update squares
+        if row > 0 {
+            draw_square(
+                graphics_context,
+                column as f32,
+                (row - 1) as f32,
+                game_grid.get_wall_count(column, row - 1),
+                None,
+                theme,
+            );
+        }
+        if row < game_grid.row_count() - 1 {
+            draw_square(
+                graphics_context,
+                column as f32,
+                row as f32,
+                game_grid.get_wall_count(column, row),
+                None,
+                theme,
+            );
+        }
+    }
+
+    // Update the stroke of all lines; there are no marks until the new position is analysed.
+    let marks = Marks {
+        move_classes: false,
+        heatmap: false,
+    };
+    redraw_lines(graphics_context, game_grid, history, None, marks, theme);
+    true
+}
+
+fn load_themes() -> (Vec<Theme>, usize) {
+    // The starting theme is selected with "--theme <name>" (a built-in theme) or
+    // "--theme <path>" (a custom theme file). Pressing T cycles through all themes.
+    let mut themes = Theme::built_in();
+    let arguments: Vec<String> = std::env::args().collect();
+    let theme_argument = arguments
+        .iter()
+        .position(|argument| argument == "--theme")
+        .and_then(|index| arguments.get(index + 1));
+    let theme_index = match theme_argument {
+        None => 0,
+        Some(name) => match themes.iter().position(|theme| &theme.name == name) {
+            Some(index) => index,
+            None => match Theme::load(name) {
+                Ok(theme) => {
+                    themes.push(theme);
+                    themes.len() - 1
+                }
+                Err(error) => {
+                    println!("{error} Using the default theme.");
+                    0
+                }
+            },
+        },
+    };
+    (themes, theme_index)
+}
+
+// An external engine playing one side of the game. The engine thinks on its own thread, so the
+// window keeps responding, and its moves arrive as events like the advisor's.
+struct EnginePlayer {
+    name: String,
+    player: usize,
+    // Positions to move in, as the walls set so far, for the engine thread.
+    requests: Sender<Vec<Wall>>,
+    // The position the engine is thinking about, if any.
+    thinking: Option<Vec<Wall>>,
+}
+
+// The engine's reply for a position.
+type EngineReply = (Vec<Wall>, Result<EngineMove, String>);
+
+fn solver_threads() -> usize {
+    // The exact solver, switched on with S, uses "--threads <count>" threads (every core by
+    // default).
+    let arguments: Vec<String> = std::env::args().collect();
+    let threads = arguments
+        .iter()
+        .position(|argument| argument == "--threads")
+        .and_then(|index| arguments.get(index + 1));
+    match threads.map(|count| (count, count.parse())) {
+        None => default_threads(),
+        Some((_, Ok(count))) if count > 0 => count,
+        Some((count, _)) => {
+            println!("Invalid thread count \"{count}\", using every core.");
+            default_threads()
+        }
+    }
+}
+
+fn start_engine(
+    game_grid: &Grid,
+    replies: mpsc::UnboundedSender<EngineReply>,
+) -> Option<EnginePlayer> {
+    // An engine is started with "--engine <command>". It plays second unless "--engine-first" is
+    // given and thinks for "--move-time <milliseconds>" (one second by default) per move.
+    let arguments: Vec<String> = std::env::args().collect();
+    let value = |name: &str| {
+        arguments
+            .iter()
+            .position(|argument| argument == name)
+            .and_then(|index| arguments.get(index + 1))
+    };
+    let command = value("--engine")?;
+    let move_time = match value("--move-time").map(|time| (time, time.parse())) {
+        None => 1000,
+        Some((_, Ok(time))) => time,
+        Some((time, Err(_))) => {
+            println!("Invalid move time \"{time}\". Using 1000 milliseconds.");
+            1000
+        }
+    };
+    let engine = ExternalEngine::start(command).and_then(|mut engine| {
+        engine.new_game(game_grid.column_count(), game_grid.row_count())?;
+        Ok(engine)
+    });
+    match engine {
+        Ok(mut engine) => {
+            println!("Playing against {}.", engine.name);
+            let name = engine.name.clone();
+            let move_time = Duration::from_millis(move_time);
+            let (requests, positions) = channel::<Vec<Wall>>();
+            thread::spawn(move || {
+                for moves in positions {
+                    let reply = engine.best_move(&moves, move_time);
+                    if replies.unbounded_send((moves, reply)).is_err() {
+                        return;
+                    }
+                }
+            });
+            Some(EnginePlayer {
+                name,
+                player: if arguments
+                    .iter()
+                    .any(|argument| argument == "--engine-first")
+                {
+                    0
+                } else {
+                    1
+                },
+                requests,
+                thinking: None,
+            })
+        }
+        Err(error) => {
+            println!("{error}");
+            None
+        }
+    }
+}
+
+fn request_engine_move(
+    engine_player: &mut Option<EnginePlayer>,
+    history: &MoveHistory,
+    game_grid: &Grid,
+) {
+    // Ask the engine for a move if it is its turn and it is not already thinking about the
+    // position.
+    let Some(opponent) = engine_player else {
+        return;
+    };
+    let column_count = game_grid.column_count();
+    let row_count = game_grid.row_count();
+    let wall_total = column_count * (row_count - 1) + (column_count - 1) * row_count;
+    if history.moves().len() as i32 == wall_total
+        || history.next_player(column_count, row_count) != opponent.player
+        || opponent.thinking.as_deref() == Some(history.moves())
+    {
+        return;
+    }
+    let moves = history.moves().to_vec();
+    if opponent.requests.send(moves.clone()).is_err() {
+        println!("{} has stopped.", opponent.name);
+        *engine_player = None;
+        return;
+    }
+    opponent.thinking = Some(moves);
+}
+
+fn engine_move(
+    engine_player: &mut Option<EnginePlayer>,
+    history: &MoveHistory,
+    game_grid: &Grid,
+    (moves, reply): EngineReply,
+) -> Option<Wall> {
+    // The engine's move if it is for the current position. An engine that fails to reply or
+    // replies with an illegal move is stopped and the game continues without it.
+    let opponent = engine_player.as_mut()?;
+    if opponent.thinking.as_ref() != Some(&moves) {
+        return None;
+    }
+    opponent.thinking = None;
+    if moves != history.moves() {
+        // The position changed while the engine was thinking.
+        return None;
+    }
+    match reply {
+        Ok(reply)
+            if game_grid.is_valid_wall(&reply.wall)
+                && !game_grid
+                    .get_wall(reply.wall.is_column, reply.wall.column, reply.wall.row)
+                    .set =>
+        {
+            println!(
+                "{} plays {}{}.",
+                opponent.name,
+                reply.wall,
+                match reply.score {
+                    Some(score) => format!(" (score {score})"),
+                    None => String::new(),
+                }
+            );
+            Some(reply.wall)
+        }
+        Ok(reply) => {
+            println!("{} played an illegal move {}.", opponent.name, reply.wall);
+            *engine_player = None;
+            None
+        }
+        Err(error) => {
+            println!("{error}");
+            *engine_player = None;
+            None
+        }
+    }
+}
+
+enum AnalysisEvent {
+    Window(DrawEvent),
+    // The analysis of a numbered position.
+    Report(u64, Report),
+    Engine(EngineReply),
+}
+
+// Open the analysis window and handle its events until it is closed.
+pub fn run() {
+    with_2d_graphics(|| {
+        let mut game_grid = Grid::new(8, 10);
+        let mut history = MoveHistory::new();
+        let mut cursor = Cursor::new();
+        let (themes, mut theme_index) = load_themes();
+        let mut show_coordinates = true;
+        let mut marks = Marks {
+            move_classes: true,
+            heatmap: false,
+        };
+        let (engine_sender, engine_replies) = mpsc::unbounded();
+        let mut engine_player = start_engine(&game_grid, engine_sender);
+
+        // The advisor runs on its own thread and its moves are highlighted when they arrive.
+        let (sender, receiver) = mpsc::unbounded();
+        let mut analyst = Analyst::start(move |number, report| {
+            let _ = sender.unbounded_send((number, report));
+        });
+        let mut report: Option<Report> = None;
+
+        let window_width: u64 = 800;
+        let window_height: u64 = 600;
+        let (canvas, events) = create_drawing_window_with_events(WindowProperties {
+            title: BindRef::from(&String::from("Dots and Boxes Analysis")),
+            size: BindRef::from(&(window_width, window_height)),
+            fullscreen: BindRef::from(&false),
+            has_decorations: BindRef::from(&true),
+            mouse_pointer: BindRef::from(&MousePointer::SystemDefault),
+        });
+
+        let (canvas_transform, mut window_transform) = calculate_transform(
+            window_width,
+            window_height,
+            game_grid.column_count(),
+            game_grid.row_count(),
+        );
+
+        canvas.draw(|graphics_context| {
+            redraw_all(
+                graphics_context,
+                canvas_transform,
+                &game_grid,
+                &history,
+                report.as_ref(),
+                &cursor,
+                &themes[theme_index],
+                show_coordinates,
+                marks,
+            );
+        });
+        analyst.analyze(&game_grid);
+        request_engine_move(&mut engine_player, &history, &game_grid);
+
+        executor::block_on(async move {
+            let mut events = stream::select(
+                stream::select(
+                    events.map(AnalysisEvent::Window),
+                    receiver.map(|(number, report)| AnalysisEvent::Report(number, report)),
+                ),
+                engine_replies.map(AnalysisEvent::Engine),
+            );
+
+            // Main event loop
+            while let Some(event) = events.next().await {
+                match event {
+                    // Results for positions that have since changed are ignored.
+                    AnalysisEvent::Report(number, latest) if analyst.is_latest(number) => {
+                        report = Some(latest);
+                        canvas.draw(|graphics_context| {
+                            redraw_lines(
+                                graphics_context,
+                                &game_grid,
+                                &history,
+                                report.as_ref(),
+                                marks,
+                                &themes[theme_index],
+                            );
+                        });
+                    }
+                    AnalysisEvent::Engine(reply) => {
+                        if let Some(wall) =
+                            engine_move(&mut engine_player, &history, &game_grid, reply)
+                        {
+                            canvas.draw(|graphics_context| {
+                                line_clicked(
+                                    graphics_context,
+                                    &mut game_grid,
+                                    &mut history,
+                                    wall.is_column,
+                                    wall.row,
+                                    wall.column,
+                                    &themes[theme_index],
+                                );
+                            });
+                            report = None;
+                            analyst.analyze(&game_grid);
+                        }
+                        // The engine moves again after completing a box.
+                        request_engine_move(&mut engine_player, &history, &game_grid);
+                    }
+                    // Window resize
+                    AnalysisEvent::Window(DrawEvent::Resize(width, height))
+                        if width >= 1.0 && height >= 1.0 =>
+                    {
+                        // Update the transform (only if the window has a valid size).
+                        window_transform = calculate_transform(
+                            width as u64,
+                            height as u64,
+                            game_grid.column_count(),
+                            game_grid.row_count(),
+                        )
+                        .1;
+                        canvas.draw(|graphics_context| {
+                            redraw_all(
+                                graphics_context,
+                                canvas_transform,
+                                &game_grid,
+                                &history,
+                                report.as_ref(),
+                                &cursor,
+                                &themes[theme_index],
+                                show_coordinates,
+                                marks,
+                            );
+                        });
+                    }
+                    AnalysisEvent::Window(DrawEvent::Pointer(
+                        PointerAction::ButtonDown,
+                        _id,
+                        state,
+                    )) if state.buttons.contains(&Button::Left) => {
+                        // Process a left click action.
+
+                        // Convert a window coordinate into a canvas coordinate (state.location_in_canvas is incorrect due to a bug).
+                        let (x, y) = window_transform.transform_point(
+                            state.location_in_window.0 as f32,
+                            state.location_in_window.1 as f32,
+                        );
+
+                        // Round toward negative infinity so clicks in the margin stay outside the grid.
+                        let mut column = x.floor() as i32;
+                        let column_remainder = x - x.floor();
+                        let mut row = y.floor() as i32;
+                        let row_remainder = y - y.floor();
+                        let is_column = if column_remainder > row_remainder {
+                            if 1.0 - column_remainder < row_remainder {
+                                column += 1; // column + 1
+                                true // column
+                            } else {
+                                false // row
+                            }
+                        } else {
+                            if 1.0 - row_remainder < column_remainder {
+                                row += 1; // row + 1
+                                false // row
+                            } else {
+                                true // column
+                            }
+                        };
+
+                        let mut changed = false;
+                        canvas.draw(|graphics_context| {
+                            changed = line_clicked(
+                                graphics_context,
+                                &mut game_grid,
+                                &mut history,
+                                is_column,
+                                row,
+                                column,
+                                &themes[theme_index],
+                            );
+                            // Keep the keyboard cursor on the last wall clicked.
+                            cursor.move_to(&game_grid, is_column, column, row);
+                            draw_cursor(graphics_context, &cursor, &themes[theme_index]);
+                        });
+                        if changed {
+                            report = None;
+                            analyst.analyze(&game_grid);
+                            request_engine_move(&mut engine_player, &history, &game_grid);
+                        }
+                    }
+                    AnalysisEvent::Window(DrawEvent::KeyDown(_scancode, Some(key))) => {
+                        // Process a keyboard action on the wall under the cursor.
+                        match key {
+                            Key::KeyUp => cursor.move_by(&game_grid, 0, -1),
+                            Key::KeyDown => cursor.move_by(&game_grid, 0, 1),
+                            Key::KeyLeft => cursor.move_by(&game_grid, -1, 0),
+                            Key::KeyRight => cursor.move_by(&game_grid, 1, 0),
+                            Key::KeyTab => cursor.toggle_orientation(&game_grid),
+                            Key::KeyEnter | Key::KeySpace => {
+                                let mut changed = false;
+                                canvas.draw(|graphics_context| {
+                                    changed = line_clicked(
+                                        graphics_context,
+                                        &mut game_grid,
+                                        &mut history,
+                                        cursor.is_column,
+                                        cursor.row,
+                                        cursor.column,
+                                        &themes[theme_index],
+                                    );
+                                });
+                                if changed {
+                                    report = None;
+                                    analyst.analyze(&game_grid);
+                                    request_engine_move(&mut engine_player, &history, &game_grid);
+                                }
+                            }
+                            Key::KeyE => {
+                                // Highlight the moves shown, none while the analysis is running.
+                                let no_moves = HashSet::new();
+                                export::export_window(
+                                    &game_grid,
+                                    &history,
+                                    &themes[theme_index],
+                                    report
+                                        .as_ref()
+                                        .map_or(&no_moves, |report| &report.optimal_moves),
+                                );
+                            }
+                            Key::KeyH => {
+                                marks.heatmap = !marks.heatmap;
+                                canvas.draw(|graphics_context| {
+                                    redraw_lines(
+                                        graphics_context,
+                                        &game_grid,
+                                        &history,
+                                        report.as_ref(),
+                                        marks,
+                                        &themes[theme_index],
+                                    );
+                                });
+                            }
+                            Key::KeyM => {
+                                marks.move_classes = !marks.move_classes;
+                                canvas.draw(|graphics_context| {
+                                    redraw_lines(
+                                        graphics_context,
+                                        &game_grid,
+                                        &history,
+                                        report.as_ref(),
+                                        marks,
+                                        &themes[theme_index],
+                                    );
+                                });
+                            }
+                            Key::KeyL => {
+                                show_coordinates = !show_coordinates;
+                                canvas.draw(|graphics_context| {
+                                    redraw_all(
+                                        graphics_context,
+                                        canvas_transform,
+                                        &game_grid,
+                                        &history,
+                                        report.as_ref(),
+                                        &cursor,
+                                        &themes[theme_index],
+                                        show_coordinates,
+                                        marks,
+                                    );
+                                });
+                            }
+                            Key::KeyS => {
+                                let threads = match analyst.solver_threads() {
+                                    Some(_) => {
+                                        println!("Analysis: advisor");
+                                        None
+                                    }
+                                    None => {
+                                        let threads = solver_threads();
+                                        println!("Analysis: exact solver on {threads} threads");
+                                        Some(threads)
+                                    }
+                                };
+                                analyst.set_solver_threads(threads);
+                                report = None;
+                                analyst.analyze(&game_grid);
+                                canvas.draw(|graphics_context| {
+                                    redraw_lines(
+                                        graphics_context,
+                                        &game_grid,
+                                        &history,
+                                        report.as_ref(),
+                                        marks,
+                                        &themes[theme_index],
+                                    );
+                                });
+                            }
+                            Key::KeyT => {
+                                theme_index = (theme_index + 1) % themes.len();
+                                println!("Theme: {}", themes[theme_index].name);
+                                canvas.draw(|graphics_context| {
+                                    redraw_all(
+                                        graphics_context,
+                                        canvas_transform,
+                                        &game_grid,
+                                        &history,
+                                        report.as_ref(),
+                                        &cursor,
+                                        &themes[theme_index],
+                                        show_coordinates,
+                                        marks,
+                                    );
+                                });
+                            }
+                            _ => {}
+                        }
+                        canvas.draw(|graphics_context| {
+                            draw_cursor(graphics_context, &cursor, &themes[theme_index]);
+                        });
+                    }
+
+                    // Ignore other events
+                    _ => {}
+                }
+            }
+        })
+    });
+}
+
+const VIEW_USAGE: &str = "Usage: dots_and_boxes view FILE|ADDRESS:PORT [--delay MILLISECONDS] \
+[--theme NAME|FILE]";
+
+enum ViewerEvent {
+    Window(DrawEvent),
+    Game(GameEvent),
+}
+
+fn redraw_view(
+    graphics_context: &mut Vec<Draw>,
+    transform: Transform2D,
+    game_grid: &Grid,
+    owners: &Owners,
+    theme: &Theme,
+) {
+    graphics_context.clear_canvas(theme.background_fill);
+    graphics_context.identity_transform();
+    graphics_context.transform(transform);
+
+    // The viewer shows the game as played, without the advisor's highlights.
+    draw_board(
+        graphics_context,
+        game_grid,
+        &HashSet::new(),
+        Some(owners),
+        theme,
+    );
+    draw_coordinates(graphics_context, game_grid, theme);
+}
+
+// Replay a game event stream (a game log or a spectator port) in a window as it arrives.
+pub fn view(arguments: &[String]) -> Result<(), String> {
+    let source = arguments
+        .first()
+        .filter(|source| !source.starts_with("--"))
+        .ok_or_else(|| String::from(VIEW_USAGE))?;
+    let stream = open_stream(source)?;
+    // Logs are replayed slowly enough to follow; live games are shown as they are played.
+    let mut delay = if source.contains(':') { 0 } else { 500 };
+    if let Some(index) = arguments.iter().position(|argument| argument == "--delay") {
+        let value = arguments
+            .get(index + 1)
+            .ok_or_else(|| format!("Missing value for --delay.\n{VIEW_USAGE}"))?;
+        delay = value
+            .parse()
+            .map_err(|_| format!("Invalid delay \"{value}\".\n{VIEW_USAGE}"))?;
+    }
+    let delay = Duration::from_millis(delay);
+
+    with_2d_graphics(move || {
+        let (sender, receiver) = mpsc::unbounded();
+        thread::spawn(move || {
+            read_events(stream, |event| {
+                let open = sender.unbounded_send(event).is_ok();
+                thread::sleep(delay);
+                open
+            });
+        });
+
+        let mut game_grid = Grid::new(8, 10);
+        let mut owners: Owners = vec![vec![None; 9]; 7];
+        let mut players = [String::new(), String::new()];
+        let (themes, theme_index) = load_themes();
+        let theme = &themes[theme_index];
+
+        let mut window_size: (u64, u64) = (800, 600);
+        let (canvas, events) = create_drawing_window_with_events(WindowProperties {
+            title: BindRef::from(&String::from("Dots and Boxes Viewer")),
+            size: BindRef::from(&window_size),
+            fullscreen: BindRef::from(&false),
+            has_decorations: BindRef::from(&true),
+            mouse_pointer: BindRef::from(&MousePointer::SystemDefault),
+        });
+        let canvas_transform = calculate_transform(
+            window_size.0,
+            window_size.1,
+            game_grid.column_count(),
+            game_grid.row_count(),
+        )
+        .0;
+        canvas.draw(|graphics_context| {
+            redraw_view(
+                graphics_context,
+                canvas_transform,
+                &game_grid,
+                &owners,
+                theme,
+            );
+        });
+
+        executor::block_on(async move {
+            let mut events = stream::select(
+                events.map(ViewerEvent::Window),
+                receiver.map(ViewerEvent::Game),
+            );
+
+            while let Some(event) = events.next().await {
+                match event {
+                    ViewerEvent::Window(DrawEvent::Resize(width, height))
+                        if width >= 1.0 && height >= 1.0 =>
+                    {
+                        window_size = (width as u64, height as u64);
+                    }
+                    ViewerEvent::Game(GameEvent::Start {
+                        column_count,
+                        row_count,
+                        players: names,
+                    }) => {
+                        println!(
+                            "{} against {} on {column_count}x{row_count}.",
+                            names[0], names[1]
+                        );
+                        game_grid = Grid::new(column_count, row_count);
+                        owners =
+                            vec![vec![None; (row_count - 1) as usize]; (column_count - 1) as usize];
+                        players = names;
+                    }
+                    ViewerEvent::Game(GameEvent::Wall { player, wall }) => {
+                        if !game_grid.is_valid_wall(&wall) {
+                            println!("Wall {wall} is outside the grid.");
+                            continue;
+                        }
+                        println!("{} plays {wall}.", players[player]);
+                        game_grid.set_wall_with_wall(&wall, true);
+                    }
+                    ViewerEvent::Game(GameEvent::Capture {
+                        player,
+                        column,
+                        row,
+                    }) => {
+                        if let Some(owner) = owners
+                            .get_mut(column as usize)
+                            .and_then(|column| column.get_mut(row as usize))
+                        {
+                            *owner = Some(player);
+                        }
+                    }
+                    ViewerEvent::Game(GameEvent::End { boxes, resigned }) => {
+                        match resigned {
+                            Some(player) => println!("{} resigns.", players[player]),
+                            None => println!(
+                                "Final score: {} {}, {} {}.",
+                                players[0], boxes[0], players[1], boxes[1]
+                            ),
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                }
+
+                // Redraw after a resize or a change to the game.
+                let canvas_transform = calculate_transform(
+                    window_size.0,
+                    window_size.1,
+                    game_grid.column_count(),
+                    game_grid.row_count(),
+                )
+                .0;
+                canvas.draw(|graphics_context| {
+                    redraw_view(
+                        graphics_context,
+                        canvas_transform,
+                        &game_grid,
+                        &owners,
+                        theme,
+                    );
+                });
+            }
+        })
+    });
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use crate::grid::Grid;
+use crate::wall::Wall;
+
+// The player (0 or 1) that completed each square, indexed by [column][row].
+pub type Owners = Vec<Vec<Option<usize>>>;
+
+#[derive(Clone)]
+pub struct MoveHistory {
+    // Set walls in the order they were set.
+    moves: Vec<Wall>,
+}
+
+impl Default for MoveHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self { moves: Vec::new() }
+    }
+
+    pub fn record(&mut self, wall: &Wall, set: bool) {
+        // Removing a wall takes it out of the history so the remaining moves keep their order.
+        self.moves.retain(|other| !same_position(other, wall));
+        if set {
+            self.moves.push(Wall { set: true, ..*wall });
+        }
+    }
+
+    pub fn toggle(&mut self, game_grid: &mut Grid, wall: &Wall) -> Wall {
+        // Set the wall if it is clear or clear it if it is set, keeping the history in step with the
+        // grid. Returns the wall as it now is.
+        let set = !game_grid
+            .get_wall(wall.is_column, wall.column, wall.row)
+            .set;
+        game_grid.set_wall_with_wall(wall, set);
+        self.record(wall, set);
+        Wall { set, ..*wall }
+    }
+
+    pub fn undo(&mut self, game_grid: &mut Grid) -> Option<Wall> {
+        // Clear the most recently set wall.
+        let wall = self.moves.pop()?;
+        game_grid.set_wall_with_wall(&wall, false);
+        Some(wall)
+    }
+
+    pub fn moves(&self) -> &[Wall] {
+        &self.moves
+    }
+
+    pub fn get_owners(&self, column_count: i32, row_count: i32) -> Owners {
+        self.replay(column_count, row_count).0
+    }
+
+    pub fn next_player(&self, column_count: i32, row_count: i32) -> usize {
+        self.replay(column_count, row_count).1
+    }
+
+    fn replay(&self, column_count: i32, row_count: i32) -> (Owners, usize) {
+        // Replay the moves with players alternating unless a move completes a square.
+        let mut owners = vec![vec![None; (row_count - 1) as usize]; (column_count - 1) as usize];
+        let mut replay_grid = Grid::new(column_count, row_count);
+        let mut player = 0;
+        for wall in &self.moves {
+            replay_grid.set_wall_with_wall(wall, true);
+
+            let mut squares = Vec::new();
+            if wall.is_column {
+                squares.push((wall.column - 1, wall.row));
+                squares.push((wall.column, wall.row));
+            } else {
+                squares.push((wall.column, wall.row - 1));
+                squares.push((wall.column, wall.row));
+            }
+            let mut completed = false;
+            for (column, row) in squares {
+                if column >= 0
+                    && column < column_count - 1
+                    && row >= 0
+                    && row < row_count - 1
+                    && replay_grid.get_wall_count(column, row) == 4
+                {
+                    owners[column as usize][row as usize] = Some(player);
+                    completed = true;
+                }
+            }
+            if !completed {
+                player = 1 - player;
+            }
+        }
+        (owners, player)
+    }
+}
+
+fn same_position(first: &Wall, second: &Wall) -> bool {
+    first.is_column == second.is_column && first.column == second.column && first.row == second.row
+}
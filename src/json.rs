@@ -0,0 +1,431 @@
+use std::fmt;
+use std::fmt::Write;
+
+// Just enough JSON for the game logs, spectator streams and results files.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Fields in the order they were written.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn parse(text: &str) -> Result<Value, String> {
+        let mut parser = Parser {
+            characters: text.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.characters.len() {
+            return Err(parser.error("unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+            _ => None,
+        }
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Compact JSON on a single line.
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Number(number) => write!(f, "{number}"),
+            Value::String(text) => write!(f, "{}", quote(text)),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{value}", quote(name))?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+pub fn quote(text: &str) -> String {
+    // The text as a JSON string.
+    let mut json = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            _ if character.is_control() => {
+                let _ = write!(json, "\\u{:04x}", character as u32);
+            }
+            _ => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// Arrays and objects nested deeper than this are refused rather than overflowing the stack.
+const MAXIMUM_DEPTH: usize = 64;
+
+struct Parser {
+    characters: Vec<char>,
+    position: usize,
+    // The arrays and objects open at the current position.
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Invalid JSON at character {}: {message}.",
+            self.position + 1
+        )
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .characters
+            .get(self.position)
+            .is_some_and(|character| character.is_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.characters.get(self.position).copied();
+        self.position += 1;
+        character
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(character) if character == expected => Ok(()),
+            _ => {
+                self.position -= 1;
+                Err(self.error(&format!("expected '{expected}'")))
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.characters.get(self.position) {
+            Some('{' | '[') if self.depth == MAXIMUM_DEPTH => Err(self.error("nested too deeply")),
+            Some('{') => self.nested(Parser::object),
+            Some('[') => self.nested(Parser::array),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            Some(character) if *character == '-' || character.is_ascii_digit() => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Value, String>) -> Result<Value, String> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, String> {
+        let end = self.position + keyword.len();
+        if end <= self.characters.len()
+            && self.characters[self.position..end]
+                .iter()
+                .copied()
+                .eq(keyword.chars())
+        {
+            self.position = end;
+            Ok(value)
+        } else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        // Skip the digits at the current position, returning how many there were.
+        let start = self.position;
+        while self
+            .characters
+            .get(self.position)
+            .is_some_and(char::is_ascii_digit)
+        {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        // An optional minus sign, an integer part without leading zeros, then optionally a fraction
+        // and an exponent, each with at least one digit.
+        let start = self.position;
+        let mut valid = true;
+        if self.characters.get(self.position) == Some(&'-') {
+            self.position += 1;
+        }
+        if self.characters.get(self.position) == Some(&'0') {
+            self.position += 1;
+        } else {
+            valid &= self.digits() > 0;
+        }
+        if self.characters.get(self.position) == Some(&'.') {
+            self.position += 1;
+            valid &= self.digits() > 0;
+        }
+        if matches!(self.characters.get(self.position), Some('e' | 'E')) {
+            self.position += 1;
+            if matches!(self.characters.get(self.position), Some('+' | '-')) {
+                self.position += 1;
+            }
+            valid &= self.digits() > 0;
+        }
+        let text: String = self.characters[start..self.position].iter().collect();
+        match text.parse::<f64>() {
+            Ok(number) if valid && number.is_finite() => Ok(Value::Number(number)),
+            _ => {
+                self.position = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+
+    fn code_unit(&mut self) -> Result<u32, String> {
+        // The four hex digits of a \u escape.
+        let end = self.position + 4;
+        let hex = self.characters.get(self.position..end).unwrap_or_default();
+        if hex.len() < 4 || !hex.iter().all(char::is_ascii_hexdigit) {
+            return Err(self.error("invalid escape"));
+        }
+        self.position = end;
+        Ok(hex
+            .iter()
+            .fold(0, |unit, digit| unit * 16 + digit.to_digit(16).unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        // Characters outside the basic plane are escaped as a surrogate pair.
+                        let mut unit = self.code_unit()?;
+                        if (0xd800..0xdc00).contains(&unit) {
+                            let high = unit;
+                            if self.next() != Some('\\') || self.next() != Some('u') {
+                                return Err(self.error("unpaired surrogate"));
+                            }
+                            let low = self.code_unit()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err(self.error("unpaired surrogate"));
+                            }
+                            unit = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        let character =
+                            char::from_u32(unit).ok_or_else(|| self.error("unpaired surrogate"))?;
+                        text.push(character);
+                    }
+                    Some(character @ ('"' | '\\' | '/')) => text.push(character),
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(character) => text.push(character),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.characters.get(self.position) == Some(&']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(values)),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.error("expected ',' or ']'"));
+                }
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.characters.get(self.position) == Some(&'}') {
+            self.position += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.expect(':')?;
+            fields.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(fields)),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.error("expected ',' or '}'"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text =
+            r#"{"event":"start","columns":5,"players":["a \"b\"","c\\d"],"over":null,"x":-1.5}"#;
+        let value = Value::parse(text).unwrap();
+        assert_eq!(value.get("columns").and_then(Value::as_i64), Some(5));
+        assert_eq!(
+            value.get("players").and_then(Value::as_array).unwrap()[0].as_str(),
+            Some("a \"b\"")
+        );
+        assert_eq!(value.to_string(), text);
+    }
+
+    #[test]
+    fn reports_the_position_of_errors() {
+        assert_eq!(
+            Value::parse(r#"{"a": [1, 2}"#),
+            Err(String::from(
+                "Invalid JSON at character 12: expected ',' or ']'."
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for text in [
+            "",
+            "{",
+            "[1,",
+            r#"{"a"}"#,
+            r#"{"a":1,}"#,
+            "[1 2]",
+            r#""unterminated"#,
+            r#""\x""#,
+            r#""\u12""#,
+            "tru",
+            "-",
+            "1.2.3",
+            "1.",
+            ".5",
+            "01",
+            "-01",
+            "1e",
+            "1e+",
+            "1e999",
+            "+1",
+            r#""\u+123""#,
+            r#""\ud83d""#,
+            r#""\ud83dx""#,
+            r#""\ud83d\u0041""#,
+            r#""\ude00""#,
+            "{} []",
+        ] {
+            assert!(Value::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn reads_numbers_and_escapes() {
+        for (text, number) in [("0", 0.0), ("-0.5", -0.5), ("10", 10.0), ("1.5E+2", 150.0)] {
+            assert_eq!(Value::parse(text), Ok(Value::Number(number)), "{text}");
+        }
+        assert_eq!(
+            Value::parse(r#""\u00e9 \ud83d\ude00""#),
+            Ok(Value::String(String::from("\u{e9} \u{1f600}")))
+        );
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Value::parse(&nested(MAXIMUM_DEPTH)).is_ok());
+        assert_eq!(
+            Value::parse(&nested(MAXIMUM_DEPTH + 1)),
+            Err(format!(
+                "Invalid JSON at character {}: nested too deeply.",
+                MAXIMUM_DEPTH + 1
+            ))
+        );
+        // Far too deep to parse recursively, and refused without reading the rest.
+        let text = format!(r#"{{"a":{}"#, "[".repeat(500_000));
+        assert!(Value::parse(&text).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+// The dots and boxes engine: the grid model, wall notation, move history and the move advisor
+// (Grid::get_optimal_moves). Drawing and image export need the "render" feature and the
+// interactive analysis window needs the "gui" feature, so headless users can depend on the engine
+// without the graphics stack. The "tui" feature draws the board in a terminal instead. The "serde"
+// feature makes Grid, Wall and SquareWalls serializable.
+//
+// The modules are private: the library is used through the types re-exported below and the
+// binary's subcommands in `commands`.
+
+mod book;
+mod classify;
+mod control;
+mod countdown;
+mod engine;
+mod evaluation;
+mod game;
+mod grid;
+mod history;
+mod json;
+mod network;
+mod observer;
+mod parity;
+mod search;
+mod server;
+mod solver;
+mod square_walls;
+mod tablebase;
+mod tournament;
+mod wall;
+
+// The window draws more than exports do, so without it parts of the board, the painters and the
+// cursor go unused.
+#[cfg(feature = "render")]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod board;
+#[cfg(feature = "render")]
+mod export;
+#[cfg(feature = "render")]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod painter;
+#[cfg(feature = "render")]
+mod theme;
+
+#[cfg(any(feature = "render", feature = "tui"))]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod cursor;
+
+#[cfg(feature = "gui")]
+mod analysis;
+#[cfg(feature = "gui")]
+mod gui;
+
+#[cfg(feature = "tui")]
+mod tui;
+
+pub use book::{install as install_book, Book};
+pub use classify::MoveClass;
+pub use control::ControlledValue;
+pub use countdown::Countdown;
+pub use evaluation::{score_losses, Evaluator};
+pub use game::Game;
+pub use grid::{Analysis, Grid, MoveOutcome, Tier};
+pub use history::{MoveHistory, Owners};
+pub use square_walls::SquareWalls;
+pub use tablebase::{install as install_tablebase, Tablebase};
+pub use wall::Wall;
+
+// The subcommands of the dots_and_boxes binary. Each takes the arguments after the subcommand
+// name, except the engine, which serves its protocol on the given input and output.
+pub mod commands {
+    pub use crate::book::run as book;
+    pub use crate::engine::run as engine;
+    pub use crate::network::{host, join};
+    pub use crate::server::run as server;
+    pub use crate::tablebase::run as tablebase;
+    pub use crate::tournament::run as tournament;
+
+    #[cfg(feature = "render")]
+    pub use crate::export::run as export;
+
+    #[cfg(feature = "gui")]
+    pub use crate::gui::{run as gui, view};
+
+    #[cfg(feature = "tui")]
+    pub use crate::tui::run as tui;
+}
@@ -1,372 +1,108 @@
-mod grid;
-mod square_walls;
-mod wall;
+use dots_and_boxes::commands;
 
-use flo_canvas::*;
-use flo_draw::binding::BindRef;
-use flo_draw::*;
+use std::env;
+use std::io;
+use std::process;
 
-use crate::grid::Grid;
-
-use futures::executor;
-use futures::prelude::*;
-
-const BACKGROUND_FILL: Color = Color::Rgba(1.0, 1.0, 1.0, 1.0); // White
-const SQUARE_FILL: [Color; 5] = [
-    BACKGROUND_FILL,                 // White
-    BACKGROUND_FILL,                 // White
-    Color::Rgba(1.0, 1.0, 0.0, 1.0), // Yellow
-    Color::Rgba(0.0, 0.0, 1.0, 1.0), // Blue
-    Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
-];
-const DOT_FILL: Color = Color::Rgba(0.0, 0.0, 0.0, 1.0); // Black
-const LINE_STROKE: Color = Color::Rgba(0.392, 0.392, 0.392, 1.0); // Gray
-const OPTIMAL_MOVE_STROKE: Color = Color::Rgba(0.0, 1.0, 0.0, 1.0); // Green
-
-const MARGIN: f32 = 0.1;
-
-fn calculate_transform(
-    width: u64,
-    height: u64,
-    columns: i32,
-    rows: i32,
-) -> (Transform2D, Transform2D) {
-    let width = width as f32;
-    let height = height as f32;
-    let columns = columns as f32;
-    let rows = rows as f32;
-
-    let window_aspect_ratio = width / height;
-    let canvas_transform = if window_aspect_ratio > (columns + MARGIN) / (rows + MARGIN) {
-        // Window aspect ratio (width:height) is greater than grid aspect ratio
-        // (columns:rows) therefore the grid size is bounded by height.
-
-        // Scale to fit grid and invert y-axis (to better match original
-        // implementation) then translate to center grid within window.
-        println!(
-            "1 Scale: {}, Translate: ({}, {})",
-            2.0 / (rows + MARGIN),
-            columns / -2.0 + 0.5,
-            rows / -2.0 + 0.5
-        );
-        Transform2D::scale(2.0 / (rows + MARGIN), -2.0 / (rows + MARGIN))
-            * Transform2D::translate(columns / -2.0 + 0.5, rows / -2.0 + 0.5)
-    } else {
-        // Window aspect ratio (width:height) is less than or equal to grid aspect ratio
-        // (columns:rows) therefore the grid size is bounded by width.
-
-        // Scale to fit grid and invert y-axis (to better match original
-        // implementation) then translate to center grid within window.
-        println!(
-            "2 Scale: {}, Translate: ({}, {})",
-            2.0 * window_aspect_ratio / (columns + MARGIN),
-            columns / -2.0 + 0.5,
-            rows / -2.0 + 0.5
-        );
-        Transform2D::scale(
-            2.0 * window_aspect_ratio / (columns + MARGIN),
-            -2.0 * window_aspect_ratio / (columns + MARGIN),
-        ) * Transform2D::translate(columns / -2.0 + 0.5, rows / -2.0 + 0.5)
-    };
-    let window_transform = canvas_transform.invert().unwrap()
-        * Transform2D::translate(-window_aspect_ratio, 1.0)
-        * Transform2D::scale(2.0 / height, -2.0 / height);
-    (canvas_transform, window_transform)
-}
-
-fn draw_square(graphics_context: &mut Vec<Draw>, column: f32, row: f32, color: Color) {
-    graphics_context.layer(LayerId(0));
-    graphics_context.new_path();
-    graphics_context.rect(column, row, column + 1.0, row + 1.0);
-    graphics_context.fill_color(color);
-    graphics_context.fill();
-}
-
-fn draw_dot(graphics_context: &mut Vec<Draw>, column: f32, row: f32) {
-    graphics_context.layer(LayerId(2));
-    graphics_context.new_path();
-    graphics_context.circle(column, row, 0.1);
-    graphics_context.fill_color(DOT_FILL);
-    graphics_context.fill();
-}
-
-fn draw_row(graphics_context: &mut Vec<Draw>, column: f32, row: f32, color: Color) {
-    graphics_context.layer(LayerId(1));
-    graphics_context.new_path();
-    graphics_context.rect(column, row - 0.1, column + 1.0, row + 0.1);
-    graphics_context.fill_color(color);
-    graphics_context.fill();
-}
-
-fn draw_column(graphics_context: &mut Vec<Draw>, column: f32, row: f32, color: Color) {
-    graphics_context.layer(LayerId(1));
-    graphics_context.new_path();
-    graphics_context.rect(column - 0.1, row, column + 0.1, row + 1.0);
-    graphics_context.fill_color(color);
-    graphics_context.fill();
-}
-
-fn redraw_all(graphics_context: &mut Vec<Draw>, transform: Transform2D, game_grid: &Grid) {
-    graphics_context.clear_canvas(BACKGROUND_FILL);
-
-    let optimal_moves = game_grid.get_optimal_moves();
-
-    graphics_context.identity_transform();
-    graphics_context.transform(transform);
-
-    let mut column_f;
-    let mut row_f;
-
-    for column in 0..game_grid.column_count() {
-        for row in 0..game_grid.row_count() {
-            column_f = column as f32;
-            row_f = row as f32;
-
-            if row < game_grid.row_count() - 1 {
-                if column < game_grid.column_count() - 1 {
-                    // Draw a square.
-                    draw_square(
-                        graphics_context,
-                        column_f,
-                        row_f,
-                        SQUARE_FILL[game_grid.get_wall_count(column, row) as usize],
-                    );
-                }
-
-                // Draw a column.
-                let wall = game_grid.get_wall(true, column, row);
-                if optimal_moves.contains(&wall) {
-                    draw_column(graphics_context, column_f, row_f, OPTIMAL_MOVE_STROKE);
-                } else if wall.set {
-                    draw_column(graphics_context, column_f, row_f, LINE_STROKE);
-                }
-            }
-            if column < game_grid.column_count() - 1 {
-                // Draw a row.
-                let wall = game_grid.get_wall(false, column, row);
-                if optimal_moves.contains(&wall) {
-                    draw_row(graphics_context, column_f, row_f, OPTIMAL_MOVE_STROKE);
-                } else if wall.set {
-                    draw_row(graphics_context, column_f, row_f, LINE_STROKE);
-                }
+fn main() {
+    let arguments: Vec<String> = env::args().collect();
+    match arguments.get(1).map(String::as_str) {
+        #[cfg(feature = "render")]
+        Some("export") => {
+            // Export an image without opening a window.
+            if let Err(error) = commands::export(&arguments[2..]) {
+                println!("{error}");
+                process::exit(1);
             }
-
-            // Draw a dot.
-            draw_dot(graphics_context, column_f, row_f);
         }
-    }
-}
-
-fn redraw_lines(graphics_context: &mut Vec<Draw>, game_grid: &Grid) {
-    graphics_context.layer(LayerId(1));
-    graphics_context.clear_layer();
-
-    let optimal_moves = game_grid.get_optimal_moves();
-    let mut wall;
-    for column in 0..game_grid.column_count() {
-        for row in 0..game_grid.row_count() {
-            if row < game_grid.row_count() - 1 {
-                // Column lines do not exist in the last row.
-                wall = game_grid.get_wall(true, column, row);
-                if optimal_moves.contains(&wall) {
-                    draw_column(
-                        graphics_context,
-                        column as f32,
-                        row as f32,
-                        OPTIMAL_MOVE_STROKE,
-                    );
-                } else if wall.set {
-                    draw_column(graphics_context, column as f32, row as f32, LINE_STROKE);
-                }
+        Some("engine") => {
+            // Serve the engine protocol on standard input and output.
+            if let Err(error) = commands::engine(io::stdin().lock(), io::stdout()) {
+                eprintln!("{error}");
+                process::exit(1);
             }
-            if column < game_grid.column_count() - 1 {
-                wall = game_grid.get_wall(false, column, row);
-                if optimal_moves.contains(&wall) {
-                    draw_row(
-                        graphics_context,
-                        column as f32,
-                        row as f32,
-                        OPTIMAL_MOVE_STROKE,
-                    );
-                } else if wall.set {
-                    draw_row(graphics_context, column as f32, row as f32, LINE_STROKE);
-                }
+        }
+        Some("tournament") => {
+            // Play bots against each other without opening a window.
+            if let Err(error) = commands::tournament(&arguments[2..]) {
+                println!("{error}");
+                process::exit(1);
             }
         }
-    }
-}
-
-fn line_clicked(
-    graphics_context: &mut Vec<Draw>,
-    game_grid: &mut Grid,
-    is_column: bool,
-    row: i32,
-    column: i32,
-) {
-    if is_column {
-        if row < 0
-            || row >= game_grid.row_count() - 1
-            || column < 0
-            || column >= game_grid.column_count()
-        {
-            return;
-        } // Click out of bounds.
-
-        // Toggle a column.
-        let wall = game_grid.get_wall(true, column, row);
-        game_grid.set_wall_with_wall(&wall, !wall.set);
-
-        // Update squares.
-        if column > 0 {
-            draw_square(
-                graphics_context,
-                (column - 1) as f32,
-                row as f32,
-                SQUARE_FILL[game_grid.get_wall_count(column - 1, row) as usize],
-            );
+        Some("host") | Some("join") => {
+            // Play against another instance over the network from the console.
+            let result = if arguments[1] == "host" {
+                commands::host(&arguments[2..])
+            } else {
+                commands::join(&arguments[2..])
+            };
+            if let Err(error) = result {
+                println!("{error}");
+                process::exit(1);
+            }
         }
-        if column < game_grid.column_count() - 1 {
-            draw_square(
-                graphics_context,
-                column as f32,
-                row as f32,
-                SQUARE_FILL[game_grid.get_wall_count(column, row) as usize],
-            );
+        Some("server") => {
+            // Answer analysis requests over HTTP on the local machine.
+            if let Err(error) = commands::server(&arguments[2..]) {
+                println!("{error}");
+                process::exit(1);
+            }
         }
-    } else {
-        if row < 0
-            || row >= game_grid.row_count()
-            || column < 0
-            || column >= game_grid.column_count() - 1
-        {
-            return;
-        } // Click out of bounds.
-
-        // Toggle a row.
-        let wall = game_grid.get_wall(false, column, row);
-        game_grid.set_wall_with_wall(&wall, !wall.set);
-
-        // Update squares
-        if row > 0 {
-            draw_square(
-                graphics_context,
-                column as f32,
-                (row - 1) as f32,
-                SQUARE_FILL[game_grid.get_wall_count(column, row - 1) as usize],
-            );
+        Some("book") => {
+            // Build an opening book from self-play or the solver.
+            if let Err(error) = commands::book(&arguments[2..]) {
+                println!("{error}");
+                process::exit(1);
+            }
         }
-        if row < game_grid.row_count() - 1 {
-            draw_square(
-                graphics_context,
-                column as f32,
-                row as f32,
-                SQUARE_FILL[game_grid.get_wall_count(column, row) as usize],
-            );
+        Some("tablebase") => {
+            // Generate perfect play for a small grid size.
+            if let Err(error) = commands::tablebase(&arguments[2..]) {
+                println!("{error}");
+                process::exit(1);
+            }
         }
+        Some("view") => run_viewer(&arguments[2..]),
+        Some("tui") => run_tui(&arguments[2..]),
+        _ => run_gui(),
     }
-
-    // Update the stroke of all lines.
-    redraw_lines(graphics_context, &game_grid);
 }
 
-fn main() {
-    with_2d_graphics(|| {
-        let mut game_grid = Grid::new(8, 10);
-
-        let window_width: u64 = 800;
-        let window_height: u64 = 600;
-        let (canvas, events) = create_drawing_window_with_events(WindowProperties {
-            title: BindRef::from(&String::from("Dots and Boxes Analysis")),
-            size: BindRef::from(&(window_width, window_height)),
-            fullscreen: BindRef::from(&false),
-            has_decorations: BindRef::from(&true),
-            mouse_pointer: BindRef::from(&MousePointer::SystemDefault),
-        });
-
-        let (canvas_transform, mut window_transform) = calculate_transform(
-            window_width,
-            window_height,
-            game_grid.column_count(),
-            game_grid.row_count(),
-        );
-
-        canvas.draw(|graphics_context| {
-            redraw_all(graphics_context, canvas_transform, &game_grid);
-        });
-
-        executor::block_on(async move {
-            let mut events = events;
-
-            // Main event loop
-            while let Some(event) = events.next().await {
-                match event {
-                    // Window resize
-                    DrawEvent::Resize(width, height) => {
-                        if width >= 1.0 && width >= 1.0 {
-                            // Update the transform (only if the window has a valid size).
-                            window_transform = calculate_transform(
-                                width as u64,
-                                height as u64,
-                                game_grid.column_count(),
-                                game_grid.row_count(),
-                            )
-                            .1;
-                            canvas.draw(|graphics_context| {
-                                redraw_all(graphics_context, canvas_transform, &game_grid);
-                            });
-                        }
-                    }
-                    DrawEvent::Pointer(PointerAction::ButtonDown, _id, state) => {
-                        if state.buttons.contains(&Button::Left) {
-                            // Process a left click action.
+#[cfg(feature = "gui")]
+fn run_gui() {
+    commands::gui();
+}
 
-                            // Convert a window coordinate into a canvas coordinate (state.location_in_canvas is incorrect due to a bug).
-                            let (x, y) = window_transform.transform_point(
-                                state.location_in_window.0 as f32,
-                                state.location_in_window.1 as f32,
-                            );
+#[cfg(feature = "gui")]
+fn run_viewer(arguments: &[String]) {
+    if let Err(error) = commands::view(arguments) {
+        println!("{error}");
+        process::exit(1);
+    }
+}
 
-                            let mut column = x as i32;
-                            let column_remainder = x % 1.0;
-                            let mut row = y as i32;
-                            let row_remainder = y % 1.0;
-                            let is_column = if column_remainder > row_remainder {
-                                if 1.0 - column_remainder < row_remainder {
-                                    column += 1; // column + 1
-                                    true // column
-                                } else {
-                                    false // row
-                                }
-                            } else {
-                                if 1.0 - row_remainder < column_remainder {
-                                    row += 1; // row + 1
-                                    false // row
-                                } else {
-                                    true // column
-                                }
-                            };
+#[cfg(not(feature = "gui"))]
+fn run_viewer(_arguments: &[String]) {
+    run_gui();
+}
 
-                            println!(
-                                "Click at x: {}, y:{}, row_f: {x}, column_f: {y} on {} ({column}, {row})",
-                                state.location_in_window.0, state.location_in_window.1, if is_column {"column"} else {"row"}
-                            );
+#[cfg(feature = "tui")]
+fn run_tui(arguments: &[String]) {
+    // Analyse positions in the terminal, for example over SSH.
+    if let Err(error) = commands::tui(arguments) {
+        println!("{error}");
+        process::exit(1);
+    }
+}
 
-                            canvas.draw(|graphics_context| {
-                                line_clicked(
-                                    graphics_context,
-                                    &mut game_grid,
-                                    is_column,
-                                    row,
-                                    column,
-                                );
-                            })
-                        }
-                    }
+#[cfg(not(feature = "tui"))]
+fn run_tui(_arguments: &[String]) {
+    println!("This build has no terminal board (rebuild with the \"tui\" feature).");
+    process::exit(1);
+}
 
-                    // Ignore other events
-                    _ => {}
-                }
-            }
-        })
-    });
+#[cfg(not(feature = "gui"))]
+fn run_gui() {
+    println!("This build has no analysis window (rebuild with the \"gui\" feature).");
+    process::exit(1);
 }
@@ -0,0 +1,533 @@
+use crate::engine::parse_dots;
+use crate::game::Game;
+use crate::observer::{Broadcast, JsonLog, Observers};
+use crate::wall::Wall;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// Two player games over TCP. The host keeps the authoritative game and validates every move; the
+// client mirrors it from the host's messages. Messages are single lines with walls in move notation.
+//
+//     Client to host            Host to client
+//     move <wall>               game <columns>x<rows> <player>   (a new game; the client's player)
+//     resign                    move <player> <wall>             (an accepted move by either player)
+//     rematch                   illegal <reason>                 (the client's move was refused)
+//     quit                      over <boxes> <boxes> [resigned <player>]
+//                               rematch                          (the host asks for a rematch)
+//                               quit
+//
+// Players are numbered 0 (moves first) and 1. The host moves first in the first game and the first
+// move alternates in rematches, which start once both sides have sent "rematch".
+//
+// Both sides are played from the console by typing a wall (e.g. "c2h"), "resign", "rematch" or
+// "quit".
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+const HOST_USAGE: &str = "Usage: dots_and_boxes host [--port PORT] [--dots COLUMNSxROWS] \
+[--log FILE] [--spectators PORT]";
+const JOIN_USAGE: &str = "Usage: dots_and_boxes join ADDRESS[:PORT]";
+
+enum Event {
+    Local(String),
+    Remote(String),
+    Disconnected,
+}
+
+fn forward_lines<R: BufRead + Send + 'static>(
+    reader: R,
+    sender: Sender<Event>,
+    wrap: fn(String) -> Event,
+    end: Option<Event>,
+) {
+    // Send each line read to the event loop, followed by the end event when the input closes.
+    thread::spawn(move || {
+        for line in reader.lines().map_while(Result::ok) {
+            if sender.send(wrap(line)).is_err() {
+                return;
+            }
+        }
+        if let Some(end) = end {
+            let _ = sender.send(end);
+        }
+    });
+}
+
+fn forward_connection(stream: &TcpStream, sender: Sender<Event>) -> Result<(), String> {
+    let reader = stream
+        .try_clone()
+        .map_err(|error| format!("Unable to read from the connection: {error}."))?;
+    forward_lines(
+        BufReader::new(reader),
+        sender,
+        Event::Remote,
+        Some(Event::Disconnected),
+    );
+    Ok(())
+}
+
+fn events(stream: &TcpStream) -> Result<Receiver<Event>, String> {
+    // Lines from the other side of the connection and from the console.
+    let (sender, receiver) = mpsc::channel();
+    forward_connection(stream, sender.clone())?;
+    forward_lines(BufReader::new(io::stdin()), sender, Event::Local, None);
+    Ok(receiver)
+}
+
+fn send<W: Write>(stream: &mut W, message: &str) -> Result<(), String> {
+    writeln!(stream, "{message}").map_err(|error| format!("Unable to send \"{message}\": {error}."))
+}
+
+fn describe_move(game: &Game, player: usize, wall: &Wall, completed: i32, me: usize) -> String {
+    let boxes = game.boxes();
+    format!(
+        "{} {wall}{}. Score: you {}, opponent {}.",
+        if player == me {
+            "You play"
+        } else {
+            "Opponent plays"
+        },
+        match (completed, player == me) {
+            (0, _) => String::new(),
+            (1, true) => String::from(" and complete a box"),
+            (1, false) => String::from(" and completes a box"),
+            (_, true) => format!(" and complete {completed} boxes"),
+            (_, false) => format!(" and completes {completed} boxes"),
+        },
+        boxes[me],
+        boxes[1 - me]
+    )
+}
+
+fn describe_turn(game: &Game, me: usize) -> String {
+    if game.is_over() {
+        let result = match game.winner() {
+            Some(player) if player == me => "You win",
+            Some(_) => "You lose",
+            None => "Draw",
+        };
+        let boxes = game.boxes();
+        let resigned = match game.resigned() {
+            Some(player) if player == me => " (you resigned)",
+            Some(_) => " (your opponent resigned)",
+            None => "",
+        };
+        format!(
+            "{result} {}-{}{resigned}. Type \"rematch\" or \"quit\".",
+            boxes[me],
+            boxes[1 - me]
+        )
+    } else if game.player() == me {
+        String::from("Your move.")
+    } else {
+        String::from("Waiting for your opponent.")
+    }
+}
+
+fn over_message(game: &Game) -> String {
+    let boxes = game.boxes();
+    match game.resigned() {
+        Some(player) => format!("over {} {} resigned {player}", boxes[0], boxes[1]),
+        None => format!("over {} {}", boxes[0], boxes[1]),
+    }
+}
+
+fn players(me: usize) -> [&'static str; 2] {
+    // Names of the players in the order they move, as told to observers.
+    if me == 0 {
+        ["host", "guest"]
+    } else {
+        ["guest", "host"]
+    }
+}
+
+pub fn host(arguments: &[String]) -> Result<(), String> {
+    let mut port = DEFAULT_PORT;
+    let mut size = (5, 5);
+    let mut observers = Observers::new();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("Missing value for {argument}.\n{HOST_USAGE}"))?;
+        match argument.as_str() {
+            "--port" => {
+                port = value
+                    .parse()
+                    .map_err(|_| format!("Invalid port \"{value}\".\n{HOST_USAGE}"))?
+            }
+            "--dots" => {
+                size = parse_dots(value).map_err(|error| format!("{error}\n{HOST_USAGE}"))?
+            }
+            "--log" => observers.add(Box::new(JsonLog::open(value)?)),
+            "--spectators" => {
+                let port = value
+                    .parse()
+                    .map_err(|_| format!("Invalid port \"{value}\".\n{HOST_USAGE}"))?;
+                observers.add(Box::new(Broadcast::listen(port)?));
+            }
+            _ => return Err(format!("Unexpected argument \"{argument}\".\n{HOST_USAGE}")),
+        }
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|error| format!("Unable to listen on port {port}: {error}."))?;
+    println!("Waiting for a player to join on port {port}.");
+    let (stream, address) = listener
+        .accept()
+        .map_err(|error| format!("Unable to accept a connection: {error}."))?;
+    println!("{address} joined.");
+    let events = events(&stream)?;
+    play_as_host(stream, events, size, &mut observers)
+}
+
+fn play_as_host<W: Write>(
+    mut stream: W,
+    events: Receiver<Event>,
+    size: (i32, i32),
+    observers: &mut Observers,
+) -> Result<(), String> {
+    // Run the host's games until either side quits, writing messages for the client to the stream.
+    let mut me = 0;
+    let mut game = Game::new(size.0, size.1);
+    let mut rematch = [false, false];
+    observers.started(&game, players(me));
+    send(
+        &mut stream,
+        &format!("game {}x{} {}", size.0, size.1, 1 - me),
+    )?;
+    println!(
+        "New {}x{} game. {}",
+        size.0,
+        size.1,
+        describe_turn(&game, me)
+    );
+
+    for event in events {
+        let (player, line) = match event {
+            Event::Local(line) => (me, line),
+            Event::Remote(line) => (1 - me, line),
+            Event::Disconnected => {
+                println!("Your opponent has disconnected.");
+                return Ok(());
+            }
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => continue,
+            Some("quit") => {
+                if player == me {
+                    let _ = send(&mut stream, "quit");
+                } else {
+                    println!("Your opponent has left.");
+                }
+                return Ok(());
+            }
+            Some("resign") => {
+                if !game.is_over() {
+                    game.resign(player);
+                    observers.ended(&game);
+                    send(&mut stream, &over_message(&game))?;
+                    println!("{}", describe_turn(&game, me));
+                }
+            }
+            Some("rematch") => {
+                if !game.is_over() {
+                    if player == me {
+                        println!("The game is not over.");
+                    } else {
+                        send(&mut stream, "illegal The game is not over.")?;
+                    }
+                    continue;
+                }
+                rematch[player] = true;
+                if rematch == [true, true] {
+                    // Swap who moves first.
+                    me = 1 - me;
+                    game = Game::new(size.0, size.1);
+                    rematch = [false, false];
+                    observers.started(&game, players(me));
+                    send(
+                        &mut stream,
+                        &format!("game {}x{} {}", size.0, size.1, 1 - me),
+                    )?;
+                    println!("Rematch. {}", describe_turn(&game, me));
+                } else if player == me {
+                    send(&mut stream, "rematch")?;
+                    println!("Waiting for your opponent to accept the rematch.");
+                } else {
+                    println!("Your opponent wants a rematch.");
+                }
+            }
+            Some(command) => {
+                // The remote player sends "move <wall>"; the local player just types the wall.
+                let notation = if player == me {
+                    command
+                } else if command == "move" {
+                    words.next().unwrap_or("")
+                } else {
+                    send(
+                        &mut stream,
+                        &format!("illegal Unknown command \"{command}\"."),
+                    )?;
+                    continue;
+                };
+                let result = notation
+                    .parse::<Wall>()
+                    .and_then(|wall| Ok((wall, game.play(player, &wall)?)));
+                match result {
+                    Ok((wall, completed)) => {
+                        observers.played(&game, player, &wall);
+                        send(&mut stream, &format!("move {player} {wall}"))?;
+                        println!("{}", describe_move(&game, player, &wall, completed, me));
+                        if game.is_over() {
+                            observers.ended(&game);
+                            send(&mut stream, &over_message(&game))?;
+                        }
+                        println!("{}", describe_turn(&game, me));
+                    }
+                    Err(error) if player == me => println!("{error}"),
+                    Err(error) => send(&mut stream, &format!("illegal {error}"))?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn join(arguments: &[String]) -> Result<(), String> {
+    let address = match arguments {
+        [address] if address.contains(':') => address.clone(),
+        [address] => format!("{address}:{DEFAULT_PORT}"),
+        _ => return Err(String::from(JOIN_USAGE)),
+    };
+    let stream = TcpStream::connect(&address)
+        .map_err(|error| format!("Unable to connect to {address}: {error}."))?;
+    println!("Connected to {address}.");
+    let events = events(&stream)?;
+    play_as_guest(stream, events)
+}
+
+fn play_as_guest<W: Write>(mut stream: W, events: Receiver<Event>) -> Result<(), String> {
+    // Run the client's side of the games until either side quits, writing messages for the host to
+    // the stream.
+    //
+    // The client's copy of the host's game, updated from the host's messages.
+    let mut game = None;
+    let mut me = 1;
+    for event in events {
+        match event {
+            Event::Local(line) => {
+                let command = line.trim();
+                match command {
+                    "" => {}
+                    "quit" => {
+                        let _ = send(&mut stream, "quit");
+                        return Ok(());
+                    }
+                    "resign" | "rematch" => send(&mut stream, command)?,
+                    _ => match command.parse::<Wall>() {
+                        Ok(wall) => send(&mut stream, &format!("move {wall}"))?,
+                        Err(error) => println!("{error}"),
+                    },
+                }
+            }
+            Event::Remote(line) => {
+                let mut words = line.split_whitespace();
+                match words.next() {
+                    Some("game") => {
+                        let size = words.next().map(parse_dots);
+                        let player = words.next().and_then(|player| player.parse().ok());
+                        match (size, player) {
+                            (Some(Ok((column_count, row_count))), Some(player)) if player < 2 => {
+                                me = player;
+                                let new_game = Game::new(column_count, row_count);
+                                println!(
+                                    "New {column_count}x{row_count} game. {}",
+                                    describe_turn(&new_game, me)
+                                );
+                                game = Some(new_game);
+                            }
+                            _ => return Err(format!("Invalid message from the host: \"{line}\".")),
+                        }
+                    }
+                    Some("move") => {
+                        let player = words.next().and_then(|player| player.parse().ok());
+                        let wall = words.next().and_then(|wall| wall.parse::<Wall>().ok());
+                        let played = match (game.as_mut(), player, wall) {
+                            (Some(game), Some(player), Some(wall)) => game
+                                .play(player, &wall)
+                                .ok()
+                                .map(|completed| (game, player, wall, completed)),
+                            _ => None,
+                        };
+                        match played {
+                            Some((game, player, wall, completed)) => {
+                                println!("{}", describe_move(game, player, &wall, completed, me));
+                                if !game.is_over() {
+                                    println!("{}", describe_turn(game, me));
+                                }
+                            }
+                            None => {
+                                return Err(format!("Invalid message from the host: \"{line}\"."))
+                            }
+                        }
+                    }
+                    Some("illegal") => println!("{}", words.collect::<Vec<&str>>().join(" ")),
+                    Some("over") => {
+                        // After the two box counts, the player who resigned, if one did.
+                        let resigned = match (words.nth(2), words.next().map(str::parse)) {
+                            (None, None) => None,
+                            (Some("resigned"), Some(Ok(player))) if player < 2 => Some(player),
+                            _ => return Err(format!("Invalid message from the host: \"{line}\".")),
+                        };
+                        if let Some(game) = game.as_mut() {
+                            if let Some(player) = resigned {
+                                game.resign(player);
+                            }
+                            println!("{}", describe_turn(game, me));
+                        }
+                    }
+                    Some("rematch") => println!("Your opponent wants a rematch."),
+                    Some("quit") => {
+                        println!("The host has left.");
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            Event::Disconnected => {
+                println!("The host has disconnected.");
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::{GameEvent, Observer};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    // Collects the host's events for inspection.
+    struct Recorder(Arc<Mutex<Vec<GameEvent>>>);
+
+    impl Observer for Recorder {
+        fn observe(&mut self, event: &GameEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn type_line(sender: &Sender<Event>, line: &str) {
+        sender.send(Event::Local(String::from(line))).unwrap();
+    }
+
+    fn wait_for(recorded: &Mutex<Vec<GameEvent>>, count: usize) {
+        // Fail rather than hang if the events never arrive.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while recorded.lock().unwrap().len() < count {
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting for {count} events."
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn wall(player: usize, notation: &str) -> GameEvent {
+        GameEvent::Wall {
+            player,
+            wall: notation.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn plays_between_a_host_and_a_client_over_loopback() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+
+        let (host_input, host_events) = mpsc::channel();
+        let host_remote = host_input.clone();
+        let shared = Arc::clone(&recorded);
+        let host = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            forward_connection(&stream, host_remote).unwrap();
+            let mut observers = Observers::new();
+            observers.add(Box::new(Recorder(shared)));
+            play_as_host(stream, host_events, (3, 3), &mut observers)
+        });
+        let (guest_input, guest_events) = mpsc::channel();
+        let stream = TcpStream::connect(address).unwrap();
+        forward_connection(&stream, guest_input.clone()).unwrap();
+        let guest = thread::spawn(move || play_as_guest(stream, guest_events));
+        wait_for(&recorded, 1);
+
+        // The host moves first. The client's copy of a1h is refused and b1h accepted.
+        type_line(&host_input, "a1h");
+        wait_for(&recorded, 2);
+        type_line(&guest_input, "a1h");
+        type_line(&guest_input, "b1h");
+        wait_for(&recorded, 3);
+
+        // The client resigns, both ask for a rematch and the client leaves.
+        type_line(&guest_input, "resign");
+        wait_for(&recorded, 4);
+        type_line(&host_input, "rematch");
+        type_line(&guest_input, "rematch");
+        wait_for(&recorded, 5);
+        type_line(&guest_input, "quit");
+        assert_eq!(guest.join().unwrap(), Ok(()));
+        assert_eq!(host.join().unwrap(), Ok(()));
+
+        let start = |players: [&str; 2]| GameEvent::Start {
+            column_count: 3,
+            row_count: 3,
+            players: players.map(String::from),
+        };
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            vec![
+                start(["host", "guest"]),
+                wall(0, "a1h"),
+                wall(1, "b1h"),
+                GameEvent::End {
+                    boxes: [0, 0],
+                    resigned: Some(1),
+                },
+                // The client moves first in the rematch.
+                start(["guest", "host"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_client_refuses_invalid_host_messages() {
+        for message in [
+            "over 0 0 resigned 2",
+            "over 0 0 resigned",
+            "over 0 0 left 1",
+        ] {
+            let (sender, events) = mpsc::channel();
+            for line in ["game 3x3 1", message] {
+                sender.send(Event::Remote(String::from(line))).unwrap();
+            }
+            drop(sender);
+            assert_eq!(
+                play_as_guest(Vec::new(), events),
+                Err(format!("Invalid message from the host: \"{message}\"."))
+            );
+        }
+        let (sender, events) = mpsc::channel();
+        for line in ["game 3x3 1", "over 0 0 resigned 0", "quit"] {
+            sender.send(Event::Remote(String::from(line))).unwrap();
+        }
+        assert_eq!(play_as_guest(Vec::new(), events), Ok(()));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::game::Game;
+use crate::json::Value;
+use crate::wall::Wall;
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+#[cfg(any(feature = "gui", test))]
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Games are observed as a stream of events, one JSON object per line:
+//
+//     {"event":"start","columns":5,"rows":5,"players":["host","guest"]}
+//     {"event":"wall","player":0,"wall":"c2h"}
+//     {"event":"capture","player":0,"column":2,"row":1}
+//     {"event":"end","boxes":[3,1],"resigned":null}
+//
+// Grid sizes count dots, players are numbered 0 (moves first) and 1, and captures give the column
+// and row of the completed square. A capture follows the wall that completed it.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    Start {
+        column_count: i32,
+        row_count: i32,
+        players: [String; 2],
+    },
+    Wall {
+        player: usize,
+        wall: Wall,
+    },
+    Capture {
+        player: usize,
+        column: i32,
+        row: i32,
+    },
+    End {
+        boxes: [i32; 2],
+        resigned: Option<usize>,
+    },
+}
+
+impl GameEvent {
+    pub fn to_json(&self) -> String {
+        let number = |value: i64| Value::Number(value as f64);
+        let event = |name: &str, mut fields: Vec<(&str, Value)>| {
+            fields.insert(0, ("event", Value::String(String::from(name))));
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (String::from(name), value))
+                    .collect(),
+            )
+        };
+        let value = match self {
+            GameEvent::Start {
+                column_count,
+                row_count,
+                players,
+            } => event(
+                "start",
+                vec![
+                    ("columns", number(*column_count as i64)),
+                    ("rows", number(*row_count as i64)),
+                    (
+                        "players",
+                        Value::Array(players.iter().cloned().map(Value::String).collect()),
+                    ),
+                ],
+            ),
+            GameEvent::Wall { player, wall } => event(
+                "wall",
+                vec![
+                    ("player", number(*player as i64)),
+                    ("wall", Value::String(wall.to_string())),
+                ],
+            ),
+            GameEvent::Capture {
+                player,
+                column,
+                row,
+            } => event(
+                "capture",
+                vec![
+                    ("player", number(*player as i64)),
+                    ("column", number(*column as i64)),
+                    ("row", number(*row as i64)),
+                ],
+            ),
+            GameEvent::End { boxes, resigned } => event(
+                "end",
+                vec![
+                    (
+                        "boxes",
+                        Value::Array(boxes.iter().map(|&boxes| number(boxes as i64)).collect()),
+                    ),
+                    (
+                        "resigned",
+                        resigned.map_or(Value::Null, |player| number(player as i64)),
+                    ),
+                ],
+            ),
+        };
+        value.to_string()
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn from_json(line: &str) -> Result<GameEvent, String> {
+        let value = Value::parse(line)?;
+        let integer = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_i64)
+                .ok_or_else(|| format!("Event \"{line}\" has no integer \"{key}\"."))
+        };
+        let player = |key: &str| match integer(key)? {
+            player @ (0 | 1) => Ok(player as usize),
+            _ => Err(format!("Event \"{line}\" has an invalid player.")),
+        };
+        let size = |key: &str| match integer(key)? {
+            size @ 2..=26 => Ok(size as i32),
+            _ => Err(format!("Event \"{line}\" has an invalid grid size.")),
+        };
+        let pair = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_array)
+                .filter(|values| values.len() == 2)
+                .ok_or_else(|| format!("Event \"{line}\" has no pair \"{key}\"."))
+        };
+        match value.get("event").and_then(Value::as_str) {
+            Some("start") => {
+                let players = pair("players")?;
+                let name = |index: usize| String::from(players[index].as_str().unwrap_or(""));
+                Ok(GameEvent::Start {
+                    column_count: size("columns")?,
+                    row_count: size("rows")?,
+                    players: [name(0), name(1)],
+                })
+            }
+            Some("wall") => Ok(GameEvent::Wall {
+                player: player("player")?,
+                wall: value
+                    .get("wall")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| format!("Event \"{line}\" has no wall."))?
+                    .parse()?,
+            }),
+            Some("capture") => Ok(GameEvent::Capture {
+                player: player("player")?,
+                column: integer("column")? as i32,
+                row: integer("row")? as i32,
+            }),
+            Some("end") => {
+                let boxes = pair("boxes")?;
+                let count = |index: usize| boxes[index].as_i64().unwrap_or(0) as i32;
+                Ok(GameEvent::End {
+                    boxes: [count(0), count(1)],
+                    resigned: match value.get("resigned") {
+                        None | Some(Value::Null) => None,
+                        Some(_) => Some(player("resigned")?),
+                    },
+                })
+            }
+            _ => Err(format!("Unknown event \"{line}\".")),
+        }
+    }
+}
+
+pub trait Observer {
+    fn observe(&mut self, event: &GameEvent);
+}
+
+// Observers of one game, told about each event as it happens.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl Observers {
+    pub fn new() -> Self {
+        Self {
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    fn send(&mut self, event: GameEvent) {
+        for observer in &mut self.observers {
+            observer.observe(&event);
+        }
+    }
+
+    pub fn started(&mut self, game: &Game, players: [&str; 2]) {
+        self.send(GameEvent::Start {
+            column_count: game.grid().column_count(),
+            row_count: game.grid().row_count(),
+            players: players.map(String::from),
+        });
+    }
+
+    pub fn played(&mut self, game: &Game, player: usize, wall: &Wall) {
+        // Called after the wall has been set in the game.
+        self.send(GameEvent::Wall {
+            player,
+            wall: Wall {
+                set: false,
+                ..*wall
+            },
+        });
+        let game_grid = game.grid();
+        for (column, row) in game_grid.adjacent_squares(wall) {
+            if game_grid.get_wall_count(column, row) == 4 {
+                self.send(GameEvent::Capture {
+                    player,
+                    column,
+                    row,
+                });
+            }
+        }
+    }
+
+    pub fn ended(&mut self, game: &Game) {
+        self.send(GameEvent::End {
+            boxes: game.boxes(),
+            resigned: game.resigned(),
+        });
+    }
+}
+
+// Appends every event to a newline delimited JSON file.
+pub struct JsonLog {
+    file: File,
+}
+
+impl JsonLog {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|error| format!("Unable to open log {path}: {error}."))?;
+        Ok(Self { file })
+    }
+}
+
+impl Observer for JsonLog {
+    fn observe(&mut self, event: &GameEvent) {
+        if let Err(error) = writeln!(self.file, "{}", event.to_json()) {
+            eprintln!("Unable to write to the game log: {error}.");
+        }
+    }
+}
+
+// Sends every event to the spectators connected to a TCP port. Spectators joining during a game
+// are first sent the events of that game so far. Each spectator is written to on its own thread,
+// so one that stops reading holds up neither the game nor the other spectators, and one that falls
+// too far behind is dropped.
+pub struct Broadcast {
+    spectators: Arc<Mutex<Spectators>>,
+}
+
+#[derive(Default)]
+struct Spectators {
+    // One writer for each spectator.
+    senders: Vec<SyncSender<String>>,
+    // The events of the game so far.
+    events: Vec<String>,
+}
+
+// Spectators that take longer than this to accept a line, or have this many lines waiting to be
+// written, are dropped.
+const SPECTATOR_TIMEOUT: Duration = Duration::from_secs(10);
+const SPECTATOR_BACKLOG: usize = 1000;
+
+fn spectator(mut stream: TcpStream) -> SyncSender<String> {
+    // The lines sent are written to the stream until it fails, when the receiver is dropped.
+    let (sender, receiver) = mpsc::sync_channel::<String>(SPECTATOR_BACKLOG);
+    let _ = stream.set_write_timeout(Some(SPECTATOR_TIMEOUT));
+    thread::spawn(move || {
+        for line in receiver {
+            if writeln!(stream, "{line}").is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+impl Broadcast {
+    pub fn listen(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|error| format!("Unable to listen for spectators on port {port}: {error}."))?;
+        println!("Spectators can watch on port {port}.");
+        Ok(Self::accept(listener))
+    }
+
+    fn accept(listener: TcpListener) -> Self {
+        let spectators = Arc::new(Mutex::new(Spectators::default()));
+        let shared = Arc::clone(&spectators);
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let sender = spectator(stream);
+                let mut spectators = shared.lock().unwrap();
+                let Spectators { senders, events } = &mut *spectators;
+                // The game so far is sent as one message so it fits however long it is.
+                if events.is_empty() || sender.try_send(events.join("\n")).is_ok() {
+                    senders.push(sender);
+                }
+            }
+        });
+        Self { spectators }
+    }
+}
+
+impl Observer for Broadcast {
+    fn observe(&mut self, event: &GameEvent) {
+        let line = event.to_json();
+        let mut spectators = self.spectators.lock().unwrap();
+        let Spectators { senders, events } = &mut *spectators;
+        if matches!(event, GameEvent::Start { .. }) {
+            events.clear();
+        }
+        events.push(line.clone());
+        // Spectators whose writer has stopped have left, and those with a full backlog are too
+        // slow to keep.
+        senders.retain(|sender| sender.try_send(line.clone()).is_ok());
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn read_events<R: BufRead>(reader: R, mut handle: impl FnMut(GameEvent) -> bool) {
+    // Pass each event to the handler until the stream ends or the handler returns false. Lines
+    // that are not events are reported and skipped.
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match GameEvent::from_json(&line) {
+            Ok(event) => {
+                if !handle(event) {
+                    return;
+                }
+            }
+            Err(error) => eprintln!("{error}"),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn open_stream(source: &str) -> Result<Box<dyn BufRead + Send>, String> {
+    // A game log file, or the address of a spectator port.
+    match File::open(source) {
+        Ok(file) => Ok(Box::new(BufReader::new(file))),
+        Err(_) if source.contains(':') => {
+            let stream = TcpStream::connect(source)
+                .map_err(|error| format!("Unable to connect to {source}: {error}."))?;
+            Ok(Box::new(BufReader::new(stream)))
+        }
+        Err(error) => Err(format!("Unable to open {source}: {error}.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Collects events for inspection.
+    struct Recorder(Arc<Mutex<Vec<GameEvent>>>);
+
+    impl Observer for Recorder {
+        fn observe(&mut self, event: &GameEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let events = [
+            GameEvent::Start {
+                column_count: 5,
+                row_count: 4,
+                players: [String::from("host"), String::from("engine:bot \"x\"")],
+            },
+            GameEvent::Wall {
+                player: 1,
+                wall: "c2h".parse().unwrap(),
+            },
+            GameEvent::Capture {
+                player: 1,
+                column: 2,
+                row: 0,
+            },
+            GameEvent::End {
+                boxes: [7, 5],
+                resigned: Some(0),
+            },
+        ];
+        for event in events {
+            assert_eq!(GameEvent::from_json(&event.to_json()), Ok(event));
+        }
+
+        // Grid sizes the viewer cannot draw are refused.
+        for (columns, rows) in [(0, 4), (5, 1), (27, 4), (5, 100000)] {
+            let line = format!(
+                r#"{{"event":"start","columns":{columns},"rows":{rows},"players":["a","b"]}}"#
+            );
+            assert!(GameEvent::from_json(&line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn spectators_that_stop_reading_do_not_hold_up_the_game() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut broadcast = Broadcast::accept(listener);
+        let stalled = TcpStream::connect(address).unwrap();
+        // Start events with long names, far more of them than the socket buffers hold, none of it
+        // read. Each start clears the events kept for new spectators.
+        let event = GameEvent::Start {
+            column_count: 5,
+            row_count: 5,
+            players: [String::from("host"), "guest".repeat(3000)],
+        };
+        broadcast.observe(&event);
+        // Wait for the spectator to be accepted.
+        while broadcast.spectators.lock().unwrap().senders.is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        for _ in 0..5000 {
+            broadcast.observe(&event);
+        }
+        // The spectator fell too far behind and was dropped.
+        assert!(broadcast.spectators.lock().unwrap().senders.is_empty());
+
+        // A spectator joining now still receives the game.
+        let watcher = TcpStream::connect(address).unwrap();
+        let mut lines = BufReader::new(watcher).lines();
+        assert!(lines.next().unwrap().unwrap().contains("\"start\""));
+        drop(stalled);
+    }
+
+    #[test]
+    fn captures_follow_the_completing_wall() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let mut observers = Observers::new();
+        observers.add(Box::new(Recorder(Arc::clone(&recorded))));
+
+        let mut game = Game::new(3, 2);
+        observers.started(&game, ["first", "second"]);
+        for (player, notation) in [(0, "a1h"), (1, "a2h"), (0, "a1v"), (1, "c1v")] {
+            let wall = notation.parse().unwrap();
+            game.play(player, &wall).unwrap();
+            observers.played(&game, player, &wall);
+        }
+        let wall = "b1v".parse().unwrap();
+        game.play(0, &wall).unwrap();
+        observers.played(&game, 0, &wall);
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.len(), 7);
+        assert_eq!(
+            recorded[6],
+            GameEvent::Capture {
+                player: 0,
+                column: 0,
+                row: 0
+            }
+        );
+    }
+}
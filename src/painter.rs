@@ -0,0 +1,129 @@
+use flo_canvas::*;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Point, Size};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+
+// Drawing primitives shared by the window, SVG and PNG output. Coordinates are in grid units (one
+// unit between adjacent dots) and higher layers are drawn over lower layers.
+pub trait Painter {
+    fn clear_layer(&mut self, layer: u64);
+
+    fn fill_rect(&mut self, layer: u64, from: (f32, f32), to: (f32, f32), color: Color);
+
+    fn fill_circle(&mut self, layer: u64, center: (f32, f32), radius: f32, color: Color);
+
+    fn stroke_line(
+        &mut self,
+        layer: u64,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    );
+
+    // Draw text centered on a point. By default the text is drawn with a bitmap font made of
+    // rectangles so that it needs no font files.
+    fn text(&mut self, layer: u64, center: (f32, f32), height: f32, text: &str, color: Color) {
+        let mut glyphs = GlyphPixels { pixels: Vec::new() };
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let _ = Text::with_baseline(text, Point::zero(), style, Baseline::Top).draw(&mut glyphs);
+
+        let pixel_size = height / FONT_6X10.character_size.height as f32;
+        let left = center.0
+            - pixel_size * (text.len() as u32 * FONT_6X10.character_size.width) as f32 / 2.0;
+        let top = center.1 - height / 2.0;
+
+        // Merge horizontal runs of pixels into single rectangles.
+        glyphs.pixels.sort();
+        let mut index = 0;
+        while index < glyphs.pixels.len() {
+            let (y, x) = glyphs.pixels[index];
+            let mut end = index + 1;
+            while end < glyphs.pixels.len() && glyphs.pixels[end] == (y, x + (end - index) as i32) {
+                end += 1;
+            }
+            self.fill_rect(
+                layer,
+                (left + x as f32 * pixel_size, top + y as f32 * pixel_size),
+                (
+                    left + (x + (end - index) as i32) as f32 * pixel_size,
+                    top + (y + 1) as f32 * pixel_size,
+                ),
+                color,
+            );
+            index = end;
+        }
+    }
+}
+
+struct GlyphPixels {
+    // Lit pixels as (y, x).
+    pixels: Vec<(i32, i32)>,
+}
+
+impl OriginDimensions for GlyphPixels {
+    fn size(&self) -> Size {
+        Size::new(u16::MAX as u32, FONT_6X10.character_size.height)
+    }
+}
+
+impl DrawTarget for GlyphPixels {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = embedded_graphics::Pixel<Self::Color>>,
+    {
+        for embedded_graphics::Pixel(point, color) in pixels {
+            if color == BinaryColor::On {
+                self.pixels.push((point.y, point.x));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Painter for Vec<Draw> {
+    fn clear_layer(&mut self, layer: u64) {
+        self.layer(LayerId(layer));
+        GraphicsContext::clear_layer(self);
+    }
+
+    fn fill_rect(&mut self, layer: u64, from: (f32, f32), to: (f32, f32), color: Color) {
+        self.layer(LayerId(layer));
+        self.new_path();
+        self.rect(from.0, from.1, to.0, to.1);
+        self.fill_color(color);
+        self.fill();
+    }
+
+    fn fill_circle(&mut self, layer: u64, center: (f32, f32), radius: f32, color: Color) {
+        self.layer(LayerId(layer));
+        self.new_path();
+        self.circle(center.0, center.1, radius);
+        self.fill_color(color);
+        self.fill();
+    }
+
+    fn stroke_line(
+        &mut self,
+        layer: u64,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    ) {
+        self.layer(LayerId(layer));
+        self.new_path();
+        self.move_to(from.0, from.1);
+        self.line_to(to.0, to.1);
+        self.line_width(width);
+        self.stroke_color(color);
+        self.stroke();
+    }
+}
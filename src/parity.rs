@@ -0,0 +1,169 @@
+use crate::control::{find_components, Component};
+use crate::grid::Grid;
+use crate::wall::Wall;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+// Long chain parity for the safe move phase. Every move that does not complete a box passes the
+// turn, and so does every move completing boxes except a double-cross, which completes two with
+// one wall. The player to move makes the last move, taking the last chain, when the turns left to
+// pass are even: the unset walls, less the unfinished boxes, plus one double-cross for each long
+// chain but the last one that the player in control declines. So the player to move wants the
+// unset walls less the unfinished boxes plus the long chains to be odd.
+//
+// Safe moves are ranked by how many random playouts of the rest of the safe phase, with the same
+// playouts after every move, end with a chain structure of the mover's parity. The playouts
+// merge, split and close the chains the move leaves behind, which is what steers the count.
+// Playouts take a while on large grids, so they only run within the time the advisor is given
+// for them (see Grid::analyze_ranked).
+
+// Enough playouts that moves are rarely tied for the best rate unless the position is symmetric.
+const PLAYOUTS: usize = 64;
+const SEED: u64 = 0;
+
+pub fn mover_has_parity(game_grid: &Grid, long_chains: i32) -> bool {
+    // Whether the player to move would take the last chain with the given number of long chains.
+    let unset_walls = game_grid.legal_moves().count() as i32;
+    let unfinished_boxes = (0..game_grid.column_count() - 1)
+        .flat_map(|column| (0..game_grid.row_count() - 1).map(move |row| (column, row)))
+        .filter(|&(column, row)| game_grid.get_wall_count(column, row) < 4)
+        .count() as i32;
+    (unset_walls - unfinished_boxes + long_chains) % 2 != 0
+}
+
+pub fn long_chain_count(game_grid: &Grid) -> i32 {
+    // The chains of three or more boxes formed so far. Loops do not change the parity.
+    find_components(game_grid)
+        .into_iter()
+        .filter(|&component| matches!(component, Component::Chain(length) if length >= 3))
+        .count() as i32
+}
+
+pub fn parity_rates(
+    game_grid: &Grid,
+    safe_moves: &HashSet<Wall>,
+    deadline: Option<Instant>,
+) -> HashMap<Wall, f64> {
+    // The share of playouts after each safe move that end with the mover's parity. Playouts are
+    // played one at a time after every move until there have been PLAYOUTS or the deadline passes,
+    // and a playout the deadline interrupts is left out, so with no time for even one playout
+    // there are no rates.
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+    let mut moves: Vec<&Wall> = safe_moves.iter().collect();
+    moves.sort_by_key(|wall| (wall.column, wall.row, wall.is_column));
+    let mut favourable = vec![0; moves.len()];
+    let mut playouts = 0;
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    'playouts: while playouts < PLAYOUTS {
+        walls.shuffle(&mut rng);
+        let mut results = Vec::with_capacity(moves.len());
+        for wall in &moves {
+            if expired() {
+                break 'playouts;
+            }
+            let mut next_grid = game_grid.clone();
+            next_grid.make_move(wall);
+            for wall in &walls {
+                // Walls only become unsafe as the playout goes on, so one pass leaves no safe
+                // move behind.
+                if is_safe(&next_grid, wall) {
+                    next_grid.make_move(wall);
+                }
+            }
+            results.push(mover_has_parity(game_grid, long_chain_count(&next_grid)));
+        }
+        for (count, result) in favourable.iter_mut().zip(results) {
+            *count += result as usize;
+        }
+        playouts += 1;
+    }
+    if playouts == 0 {
+        return HashMap::new();
+    }
+    moves
+        .into_iter()
+        .zip(favourable)
+        .map(|(wall, count)| (*wall, count as f64 / playouts as f64))
+        .collect()
+}
+
+fn is_safe(game_grid: &Grid, wall: &Wall) -> bool {
+    !game_grid
+        .get_wall(wall.is_column, wall.column, wall.row)
+        .set
+        && game_grid
+            .adjacent_squares(wall)
+            .into_iter()
+            .all(|(column, row)| game_grid.get_wall_count(column, row) < 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Tier;
+    use std::time::Duration;
+
+    #[test]
+    fn parity_decides_control_of_loony_endgames() {
+        // In a loony endgame of chains and loops the player to move has the parity exactly when
+        // the short chains leave them in control.
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut tested = 0;
+        while tested < 20 {
+            let mut game_grid = Grid::new(5, 4);
+            let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+            walls.shuffle(&mut rng);
+            for wall in &walls {
+                if is_safe(&game_grid, wall) {
+                    game_grid.make_move(wall);
+                }
+            }
+            let Some(value) = game_grid.controlled_value() else {
+                continue;
+            };
+            tested += 1;
+            assert_eq!(
+                mover_has_parity(&game_grid, value.long_chains.len() as i32),
+                value.mover_in_control
+            );
+        }
+    }
+
+    #[test]
+    fn the_advisor_prefers_safe_moves_with_the_parity() {
+        let game_grid = Grid::new(5, 5);
+        let limit = Duration::from_secs(600);
+        let analysis = game_grid.analyze_ranked(limit);
+        assert_eq!(analysis.tier, Some(Tier::Safe));
+        let rates = &analysis.parity_rates;
+        assert_eq!(rates.len(), game_grid.analyze_heuristics().moves.len());
+        let best = rates.values().copied().fold(0.0, f64::max);
+        assert!(best > rates.values().copied().fold(1.0, f64::min));
+
+        // The best ranked moves are the advisor's moves, ahead of the rest.
+        assert!(analysis.moves.iter().all(|wall| rates[wall] == best));
+        let ranking = &analysis.ranking;
+        assert_eq!(ranking, &game_grid.ranked_moves(limit));
+        assert!(ranking[..analysis.moves.len()]
+            .iter()
+            .all(|wall| analysis.moves.contains(wall)));
+        assert!(ranking
+            .windows(2)
+            .all(|pair| rates[&pair[0]] >= rates[&pair[1]]));
+    }
+
+    #[test]
+    fn playouts_stop_at_the_deadline() {
+        let game_grid = Grid::new(5, 5);
+        let moves = game_grid.analyze_heuristics().moves;
+        assert!(parity_rates(&game_grid, &moves, Some(Instant::now())).is_empty());
+        // Without time for playouts the advisor leaves every safe move.
+        let analysis = game_grid.analyze();
+        assert!(analysis.parity_rates.is_empty());
+        assert_eq!(analysis.moves, moves);
+    }
+}
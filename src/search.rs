@@ -0,0 +1,298 @@
+use crate::grid::Grid;
+use crate::wall::Wall;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Iterative deepening alpha-beta search. Scores are in boxes from the point of view of the player
+// to move: the boxes they complete minus the boxes their opponent completes over the searched
+// moves. Completing a box earns another move, so a capture is followed by a move by the same
+// player rather than the opponent.
+
+const INFINITY: i32 = 1_000_000;
+// Nodes searched between checks of the clock and the cancellation token.
+const CHECK_INTERVAL: u64 = 1024;
+
+// Shared between the search and whoever wants to stop it. Cloned tokens cancel together.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SearchLimits {
+    pub time: Duration,
+    // The deepest iteration in plies, or None to deepen until time runs out or the game ends.
+    pub depth: Option<i32>,
+}
+
+// The result of one completed iteration.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Progress {
+    pub depth: i32,
+    // Nodes searched so far, over all iterations.
+    pub nodes: u64,
+    pub score: i32,
+    // The best line found, starting with the move to play.
+    pub principal_variation: Vec<Wall>,
+}
+
+pub fn search<F: FnMut(&Progress)>(
+    game_grid: &Grid,
+    limits: SearchLimits,
+    cancel: &CancelToken,
+    mut progress: F,
+) -> Option<Progress> {
+    // Search one ply deeper each iteration, reporting each completed iteration, and return the last
+    // one completed when time runs out or the search is cancelled. The first iteration is always
+    // completed so there is a move to play whenever a wall is unset.
+    let remaining = game_grid.legal_moves().count() as i32;
+    let last_depth = limits.depth.unwrap_or(remaining).min(remaining);
+    let mut searcher = Searcher {
+        grid: game_grid.clone(),
+        nodes: 0,
+        deadline: Instant::now() + limits.time,
+        cancel,
+        can_stop: false,
+        stopped: false,
+    };
+    let mut best: Option<Progress> = None;
+    for depth in 1..=last_depth {
+        let hint = best
+            .as_ref()
+            .map_or(Vec::new(), |best| best.principal_variation.clone());
+        let mut line = Vec::new();
+        let Some(score) = searcher.negamax(depth, -INFINITY, INFINITY, &hint, &mut line) else {
+            break;
+        };
+        let result = Progress {
+            depth,
+            nodes: searcher.nodes,
+            score,
+            principal_variation: line,
+        };
+        progress(&result);
+        best = Some(result);
+        searcher.can_stop = true;
+        if searcher.should_stop() {
+            break;
+        }
+    }
+    best
+}
+
+struct Searcher<'a> {
+    grid: Grid,
+    nodes: u64,
+    deadline: Instant,
+    cancel: &'a CancelToken,
+    // Stopping is only allowed once an iteration has completed.
+    can_stop: bool,
+    stopped: bool,
+}
+
+impl Searcher<'_> {
+    fn should_stop(&mut self) -> bool {
+        if self.can_stop && (self.cancel.is_cancelled() || Instant::now() >= self.deadline) {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn negamax(
+        &mut self,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        hint: &[Wall],
+        line: &mut Vec<Wall>,
+    ) -> Option<i32> {
+        // The score of the position searched depth plies deep, with the best line in line, or None
+        // if the search was stopped.
+        self.nodes += 1;
+        if self.stopped || (self.nodes.is_multiple_of(CHECK_INTERVAL) && self.should_stop()) {
+            return None;
+        }
+        line.clear();
+        if depth == 0 {
+            return Some(0);
+        }
+        let moves = ordered_moves(&self.grid, hint.first());
+        if moves.is_empty() {
+            return Some(0);
+        }
+
+        let mut best = -INFINITY;
+        let mut child_line = Vec::new();
+        for wall in moves {
+            let child_hint = match hint.split_first() {
+                Some((first, rest)) if *first == wall => rest,
+                _ => &[],
+            };
+            let outcome = self.grid.make_move(&wall);
+            let score = if outcome.moves_again {
+                // The same player moves again, so the window is shifted rather than negated.
+                let completed = outcome.completed;
+                self.negamax(
+                    depth - 1,
+                    alpha - completed,
+                    beta - completed,
+                    child_hint,
+                    &mut child_line,
+                )
+                .map(|score| score + completed)
+            } else {
+                self.negamax(depth - 1, -beta, -alpha, child_hint, &mut child_line)
+                    .map(|score| -score)
+            };
+            self.grid.unmake_move(&wall);
+            let score = score?;
+            if score > best {
+                best = score;
+                line.clear();
+                line.push(wall);
+                line.extend_from_slice(&child_line);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+pub(crate) fn ordered_moves(game_grid: &Grid, first: Option<&Wall>) -> Vec<Wall> {
+    // The advisor's heuristic moves first (shortest sacrifices first), then the remaining captures,
+    // safe moves and sacrifices, with the previous iteration's move for this position ahead of all.
+    // The heuristics skip the books and playouts, which are too slow to run at every node.
+    let analysis = game_grid.analyze_heuristics();
+    let mut moves: Vec<Wall> = analysis.moves.iter().copied().collect();
+    moves.sort_by_key(|wall| {
+        (
+            analysis.chain_lengths.get(wall).copied().unwrap_or(0),
+            wall.column,
+            wall.row,
+            wall.is_column,
+        )
+    });
+    let mut rest: Vec<Wall> = game_grid
+        .legal_moves()
+        .filter(|wall| !analysis.moves.contains(wall))
+        .collect();
+    rest.sort_by_key(|wall| {
+        let counts: Vec<i32> = game_grid
+            .adjacent_squares(wall)
+            .into_iter()
+            .map(|(column, row)| game_grid.get_wall_count(column, row))
+            .collect();
+        if counts.contains(&3) {
+            0
+        } else if counts.iter().all(|&count| count < 2) {
+            1
+        } else {
+            2
+        }
+    });
+    moves.extend(rest);
+    if let Some(position) = first.and_then(|first| moves.iter().position(|wall| wall == first)) {
+        let wall = moves.remove(position);
+        moves.insert(0, wall);
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn unlimited() -> SearchLimits {
+        SearchLimits {
+            time: Duration::from_secs(60),
+            depth: None,
+        }
+    }
+
+    fn full_search(game_grid: &mut Grid) -> i32 {
+        // The exact score by trying every move, without pruning.
+        let moves: Vec<Wall> = game_grid.legal_moves().collect();
+        if moves.is_empty() {
+            return 0;
+        }
+        let mut best = -INFINITY;
+        for wall in moves {
+            let outcome = game_grid.make_move(&wall);
+            let score = if outcome.moves_again {
+                outcome.completed + full_search(game_grid)
+            } else {
+                -full_search(game_grid)
+            };
+            game_grid.unmake_move(&wall);
+            best = best.max(score);
+        }
+        best
+    }
+
+    #[test]
+    fn matches_a_full_search_of_small_positions() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..10 {
+            let mut game_grid = Grid::new(4, 3);
+            let mut moves: Vec<Wall> = game_grid.legal_moves().collect();
+            moves.shuffle(&mut rng);
+            for wall in &moves[..10] {
+                game_grid.make_move(wall);
+            }
+            let mut depths = Vec::new();
+            let result = search(&game_grid, unlimited(), &CancelToken::new(), |progress| {
+                depths.push(progress.depth)
+            })
+            .unwrap();
+            assert_eq!(depths, (1..=7).collect::<Vec<i32>>());
+            assert_eq!(result.principal_variation.len(), 7);
+            assert_eq!(result.score, full_search(&mut game_grid));
+        }
+    }
+
+    #[test]
+    fn cancelling_keeps_the_first_iteration() {
+        let game_grid = Grid::new(5, 5);
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result = search(&game_grid, unlimited(), &cancel, |_| {}).unwrap();
+        assert_eq!(result.depth, 1);
+        assert_eq!(result.principal_variation.len(), 1);
+    }
+
+    #[test]
+    fn respects_the_time_budget() {
+        let limits = SearchLimits {
+            time: Duration::from_millis(100),
+            depth: None,
+        };
+        let start = Instant::now();
+        let result = search(&Grid::new(6, 6), limits, &CancelToken::new(), |_| {});
+        assert!(result.is_some());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
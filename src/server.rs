@@ -0,0 +1,628 @@
+use crate::engine::{move_score, parse_dots};
+use crate::game::Game;
+use crate::grid::Tier;
+use crate::json::Value;
+use crate::search::CancelToken;
+use crate::solver::{default_threads, solve};
+use crate::wall::Wall;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+// A local HTTP server answering analysis requests with JSON.
+//
+//     POST /analyze   The advisor's recommendation for a position, with safe moves ranked for at
+//                     most half a second. Positions with too many walls left are refused.
+//     POST /move      The position after a move, and the boxes the move captured.
+//     POST /solve     The exact score of the rest of the game and every move reaching it, found
+//                     by the parallel solver. Only positions with few walls left are accepted, a
+//                     few at a time, and each solve is abandoned when its time runs out.
+//
+// Positions are sent either as JSON, {"dots": "5x5", "moves": ["a1h", "b2v"]}, or as text giving
+// the grid size followed by the walls set so far, "5x5 a1h b2v". The move to make is given by a
+// "move" field in JSON or on a second line of text. Moves are replayed from the start of the game
+// so the server knows whose turn it is and who owns each box.
+
+pub const DEFAULT_PORT: u16 = 7880;
+
+const SERVER_USAGE: &str = "Usage: dots_and_boxes server [--port PORT] [--threads COUNT]";
+// Requests larger than this are refused, as are request lines and headers longer than the line
+// limit or more headers than the header limit.
+const MAXIMUM_BODY: usize = 1 << 20;
+const MAXIMUM_LINE: usize = 8 << 10;
+const MAXIMUM_HEADERS: usize = 100;
+// How long a client may take to send its request.
+const READ_TIME: Duration = Duration::from_secs(10);
+// Positions with more unset walls than this are refused by /analyze, and the advisor ranks safe
+// moves for at most the analysis time.
+const MAXIMUM_ANALYZE_WALLS: usize = 1000;
+const ANALYZE_TIME: Duration = Duration::from_millis(500);
+// Positions with more unset walls than this are refused by /solve.
+const MAXIMUM_SOLVE_WALLS: usize = 30;
+// The solves running at once, and how long each may take.
+const MAXIMUM_SOLVES: usize = 2;
+const SOLVE_TIME: Duration = Duration::from_secs(30);
+
+pub struct Server {
+    threads: usize,
+    solves: AtomicUsize,
+}
+
+fn number(value: i64) -> Value {
+    Value::Number(value as f64)
+}
+
+fn walls_json(walls: &[Wall]) -> Value {
+    Value::Array(
+        walls
+            .iter()
+            .map(|wall| Value::String(wall.to_string()))
+            .collect(),
+    )
+}
+
+fn sorted(walls: impl IntoIterator<Item = Wall>) -> Vec<Wall> {
+    // Walls in notation order so responses are repeatable.
+    let mut walls: Vec<Wall> = walls.into_iter().collect();
+    walls.sort_by_key(|wall| (wall.column, wall.row, wall.is_column));
+    walls
+}
+
+fn parse_position(body: &str) -> Result<(Game, Option<Wall>), String> {
+    // The game reached by the position's moves and the move to make next, if one was given.
+    let (dots, moves, next) = if body.trim_start().starts_with('{') {
+        let value = Value::parse(body)?;
+        let dots = value
+            .get("dots")
+            .and_then(Value::as_str)
+            .ok_or("The position has no \"dots\" grid size.")?;
+        let moves = match value.get("moves") {
+            None => Vec::new(),
+            Some(Value::String(moves)) => moves.split_whitespace().map(String::from).collect(),
+            Some(Value::Array(moves)) => moves
+                .iter()
+                .map(|wall| wall.as_str().map(String::from))
+                .collect::<Option<Vec<String>>>()
+                .ok_or("The position's moves must be walls in move notation.")?,
+            Some(_) => {
+                return Err(String::from(
+                    "The position's moves must be a list of walls.",
+                ))
+            }
+        };
+        let next = match value.get("move") {
+            None => None,
+            Some(wall) => Some(String::from(
+                wall.as_str()
+                    .ok_or("The move must be a wall in move notation.")?,
+            )),
+        };
+        (String::from(dots), moves, next)
+    } else {
+        let mut lines = body.lines();
+        let mut words = lines.next().unwrap_or("").split_whitespace();
+        let dots = words.next().ok_or("The position is empty.")?;
+        let moves = words.map(String::from).collect();
+        let next = lines.next().map(str::trim).filter(|line| !line.is_empty());
+        (String::from(dots), moves, next.map(String::from))
+    };
+
+    let (column_count, row_count) = parse_dots(&dots)?;
+    let mut game = Game::new(column_count, row_count);
+    for notation in &moves {
+        let wall: Wall = notation.parse()?;
+        game.play(game.player(), &wall)?;
+    }
+    let next = match next {
+        Some(notation) => Some(notation.parse::<Wall>()?),
+        None => None,
+    };
+    Ok((game, next))
+}
+
+fn position_fields(game: &Game) -> Vec<(String, Value)> {
+    let game_grid = game.grid();
+    let moves: Vec<Wall> = game.history().moves().to_vec();
+    let boxes = game.boxes();
+    vec![
+        (
+            String::from("dots"),
+            Value::String(format!(
+                "{}x{}",
+                game_grid.column_count(),
+                game_grid.row_count()
+            )),
+        ),
+        (String::from("moves"), walls_json(&moves)),
+        (String::from("player"), number(game.player() as i64)),
+        (
+            String::from("boxes"),
+            Value::Array(vec![number(boxes[0] as i64), number(boxes[1] as i64)]),
+        ),
+        (String::from("over"), Value::Bool(game.is_over())),
+    ]
+}
+
+fn analyze(body: &str) -> Result<Value, String> {
+    let (game, _) = parse_position(body)?;
+    let unset_walls = game.grid().legal_moves().count();
+    if unset_walls > MAXIMUM_ANALYZE_WALLS {
+        return Err(format!(
+            "The position has {unset_walls} unset walls, more than the {MAXIMUM_ANALYZE_WALLS} \
+that can be analyzed."
+        ));
+    }
+    let analysis = game.grid().analyze_ranked(ANALYZE_TIME);
+    let recommended = sorted(analysis.moves);
+    let mut fields = position_fields(&game);
+    fields.push((
+        String::from("tier"),
+        match analysis.tier {
+            Some(Tier::Capture) => Value::String(String::from("capture")),
+            Some(Tier::Safe) => Value::String(String::from("safe")),
+            Some(Tier::Sacrifice) => Value::String(String::from("sacrifice")),
+            None => Value::Null,
+        },
+    ));
+    fields.push((String::from("recommended"), walls_json(&recommended)));
+    fields.push((
+        String::from("chain_lengths"),
+        Value::Object(
+            sorted(analysis.chain_lengths.keys().copied())
+                .into_iter()
+                .map(|wall| {
+                    (
+                        wall.to_string(),
+                        number(analysis.chain_lengths[&wall] as i64),
+                    )
+                })
+                .collect(),
+        ),
+    ));
+    let countdown = game.grid().countdown();
+    let opener = if countdown.mover_opens {
+        game.player()
+    } else {
+        1 - game.player()
+    };
+    fields.push((
+        String::from("safe_moves"),
+        number(countdown.safe_moves as i64),
+    ));
+    fields.push((String::from("opens_first_chain"), number(opener as i64)));
+    fields.push((
+        String::from("evaluation"),
+        match recommended.first() {
+            Some(wall) => Value::Object(vec![
+                (String::from("move"), Value::String(wall.to_string())),
+                (
+                    String::from("score"),
+                    number(move_score(game.grid(), wall) as i64),
+                ),
+            ]),
+            None => Value::Null,
+        },
+    ));
+    Ok(Value::Object(fields))
+}
+
+fn cancel_after(cancel: &CancelToken, time: Duration) -> mpsc::Sender<()> {
+    // Cancel the token when the time runs out, unless the returned sender is dropped first.
+    let (finished, timer) = mpsc::channel();
+    let cancel = cancel.clone();
+    thread::spawn(move || {
+        if timer.recv_timeout(time) == Err(mpsc::RecvTimeoutError::Timeout) {
+            cancel.cancel();
+        }
+    });
+    finished
+}
+
+fn solve_position(body: &str, threads: usize, cancel: &CancelToken) -> Result<Value, String> {
+    let (game, _) = parse_position(body)?;
+    let unset_walls = game.grid().legal_moves().count();
+    if unset_walls > MAXIMUM_SOLVE_WALLS {
+        return Err(format!(
+            "The position has {unset_walls} unset walls, more than the {MAXIMUM_SOLVE_WALLS} that \
+can be solved."
+        ));
+    }
+    let solution = solve(game.grid(), threads, cancel).ok_or_else(|| {
+        format!(
+            "The position was not solved within {} seconds.",
+            SOLVE_TIME.as_secs()
+        )
+    })?;
+    let mut fields = position_fields(&game);
+    fields.push((String::from("score"), number(solution.score as i64)));
+    fields.push((String::from("best_moves"), walls_json(&solution.best_moves)));
+    fields.push((String::from("nodes"), number(solution.nodes as i64)));
+    Ok(Value::Object(fields))
+}
+
+fn play(body: &str) -> Result<Value, String> {
+    let (mut game, next) = parse_position(body)?;
+    let wall = next.ok_or("No move was given.")?;
+    let player = game.player();
+    game.play(player, &wall)?;
+    let game_grid = game.grid();
+    let captures = game_grid
+        .adjacent_squares(&wall)
+        .into_iter()
+        .filter(|&(column, row)| game_grid.get_wall_count(column, row) == 4)
+        .map(|(column, row)| {
+            Value::Object(vec![
+                (String::from("column"), number(column as i64)),
+                (String::from("row"), number(row as i64)),
+            ])
+        })
+        .collect();
+    let mut fields = position_fields(&game);
+    fields.push((String::from("mover"), number(player as i64)));
+    fields.push((String::from("captures"), Value::Array(captures)));
+    Ok(Value::Object(fields))
+}
+
+impl Server {
+    pub fn new(threads: usize) -> Self {
+        // A server solving on the given number of threads.
+        Self {
+            threads,
+            solves: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> (u16, String) {
+        // The status and JSON body of the response to a request.
+        let result = match (method, path) {
+            ("POST", "/analyze") => analyze(body),
+            ("POST", "/move") => play(body),
+            ("POST", "/solve") => {
+                if self.solves.fetch_add(1, Ordering::SeqCst) >= MAXIMUM_SOLVES {
+                    self.solves.fetch_sub(1, Ordering::SeqCst);
+                    return (
+                        503,
+                        error_json("The server is busy solving, try again later."),
+                    );
+                }
+                let cancel = CancelToken::new();
+                let timer = cancel_after(&cancel, SOLVE_TIME);
+                let result = solve_position(body, self.threads, &cancel);
+                drop(timer);
+                self.solves.fetch_sub(1, Ordering::SeqCst);
+                result
+            }
+            (_, "/analyze") | (_, "/move") | (_, "/solve") => {
+                return (405, error_json(&format!("Use POST for {path}.")));
+            }
+            _ => return (404, error_json(&format!("There is nothing at {path}."))),
+        };
+        match result {
+            Ok(value) => (200, value.to_string()),
+            Err(error) => (400, error_json(&error)),
+        }
+    }
+}
+
+fn error_json(message: &str) -> String {
+    Value::Object(vec![(
+        String::from("error"),
+        Value::String(String::from(message)),
+    )])
+    .to_string()
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, String> {
+    // A line of the request head, refusing lines that are too long or cut short.
+    let mut line = String::new();
+    reader
+        .take(MAXIMUM_LINE as u64)
+        .read_line(&mut line)
+        .map_err(|error| error.to_string())?;
+    if !line.ends_with('\n') {
+        return Err(String::from(
+            "The request line or a header is too long or incomplete.",
+        ));
+    }
+    Ok(line)
+}
+
+fn read_request<R: Read>(stream: R) -> Result<(String, String, String), String> {
+    // The method, path and body of an HTTP request.
+    let mut reader = BufReader::new(stream);
+    let request_line = read_line(&mut reader)?;
+    let mut words = request_line.split_whitespace();
+    let (method, path) = match (words.next(), words.next()) {
+        (Some(method), Some(path)) => (String::from(method), String::from(path)),
+        _ => return Err(String::from("Malformed request line.")),
+    };
+    let mut length = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_line(&mut reader)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAXIMUM_HEADERS {
+            return Err(String::from("The request has too many headers."));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| String::from("Invalid Content-Length."))?;
+            }
+        }
+    }
+    if length > MAXIMUM_BODY {
+        return Err(String::from("The request is too large."));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|error| error.to_string())?;
+    let body = String::from_utf8(body).map_err(|_| String::from("The body is not UTF-8."))?;
+    Ok((method, path, body))
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &str) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+pub fn serve(listener: TcpListener, threads: usize) {
+    // Answer each connection on its own thread, one request per connection.
+    let server = Arc::new(Server::new(threads));
+    for mut stream in listener.incoming().map_while(Result::ok) {
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            let request = stream
+                .set_read_timeout(Some(READ_TIME))
+                .map_err(|error| error.to_string())
+                .and_then(|_| read_request(&stream));
+            match request {
+                Ok((method, path, body)) => {
+                    let (status, response) = server.handle(&method, &path, &body);
+                    respond(&mut stream, status, &response);
+                }
+                Err(error) => respond(&mut stream, 400, &error_json(&error)),
+            }
+        });
+    }
+}
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    let mut port = DEFAULT_PORT;
+    let mut threads = default_threads();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("Missing value for {argument}.\n{SERVER_USAGE}"))?;
+        let invalid = || format!("Invalid {argument} \"{value}\".\n{SERVER_USAGE}");
+        match argument.as_str() {
+            "--port" => port = value.parse().map_err(|_| invalid())?,
+            "--threads" => {
+                threads = match value.parse() {
+                    Ok(count) if count > 0 => count,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(String::from(SERVER_USAGE)),
+        }
+    }
+    // Only local clients can connect.
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|error| format!("Unable to listen on port {port}: {error}."))?;
+    println!("Serving analysis on http://127.0.0.1:{port} with {threads} solver threads.");
+    serve(listener, threads);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::ToSocketAddrs;
+
+    fn handle(method: &str, path: &str, body: &str) -> (u16, String) {
+        Server::new(1).handle(method, path, body)
+    }
+
+    fn request<A: ToSocketAddrs>(
+        address: A,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> Result<(u16, String), String> {
+        // A minimal client for the server: the status and body of the response.
+        let mut stream = TcpStream::connect(address).map_err(|error| error.to_string())?;
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\
+    Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .map_err(|error| error.to_string())?;
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|error| error.to_string())?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or("Malformed response.")?;
+        let status = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or("Malformed status line.")?;
+        Ok((status, String::from(body)))
+    }
+
+    fn start() -> std::net::SocketAddr {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, 2));
+        address
+    }
+
+    #[test]
+    fn analyzes_a_position() {
+        let address = start();
+        let (status, body) = request(
+            address,
+            "POST",
+            "/analyze",
+            r#"{"dots": "3x3", "moves": ["a1h", "a1v", "b1v"]}"#,
+        )
+        .unwrap();
+        assert_eq!(status, 200);
+        let value = Value::parse(&body).unwrap();
+        assert_eq!(value.get("tier").and_then(Value::as_str), Some("capture"));
+        assert_eq!(value.get("player").and_then(Value::as_i64), Some(1));
+        assert_eq!(
+            value.get("recommended"),
+            Some(&Value::Array(vec![Value::String(String::from("a2h"))]))
+        );
+        // Four safe moves are left once the box is taken, so the player taking it opens a chain.
+        assert_eq!(value.get("safe_moves").and_then(Value::as_i64), Some(4));
+        assert_eq!(
+            value.get("opens_first_chain").and_then(Value::as_i64),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn reports_sacrifice_chain_lengths() {
+        let (status, body) = handle("POST", "/analyze", "2x3 a1v b1v a2v b2v");
+        assert_eq!(status, 200);
+        let value = Value::parse(&body).unwrap();
+        assert_eq!(value.get("tier").and_then(Value::as_str), Some("sacrifice"));
+        let chain_lengths = value.get("chain_lengths").unwrap();
+        assert_eq!(chain_lengths.get("a2h").and_then(Value::as_i64), Some(2));
+    }
+
+    #[test]
+    fn applies_a_move() {
+        let address = start();
+        let (status, body) = request(address, "POST", "/move", "3x3 a1h a1v b1v\na2h").unwrap();
+        assert_eq!(status, 200);
+        let value = Value::parse(&body).unwrap();
+        assert_eq!(value.get("mover").and_then(Value::as_i64), Some(1));
+        assert_eq!(value.get("player").and_then(Value::as_i64), Some(1));
+        assert_eq!(
+            value.get("captures").map(Value::to_string),
+            Some(String::from(r#"[{"column":0,"row":0}]"#))
+        );
+    }
+
+    #[test]
+    fn solves_a_position() {
+        let address = start();
+        let (status, body) = request(address, "POST", "/solve", "3x3 a1h a1v b1v b2h").unwrap();
+        assert_eq!(status, 200);
+        let value = Value::parse(&body).unwrap();
+        assert_eq!(value.get("player").and_then(Value::as_i64), Some(0));
+        assert_eq!(value.get("score").and_then(Value::as_i64), Some(2));
+        assert_eq!(
+            value.get("best_moves").map(Value::to_string),
+            Some(String::from(r#"["a2h"]"#))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(handle("POST", "/move", "3x3 a1h\na1h").0, 400);
+        assert_eq!(handle("GET", "/analyze", "").0, 405);
+        assert_eq!(handle("GET", "/solve", "").0, 405);
+        assert_eq!(handle("POST", "/other", "").0, 404);
+    }
+
+    #[test]
+    fn rejects_oversized_requests() {
+        // Deeply nested JSON and huge grids are refused instead of exhausting the server.
+        let nested = format!(r#"{{"a":{}"#, "[".repeat(500_000));
+        assert_eq!(handle("POST", "/analyze", &nested).0, 400);
+        assert_eq!(
+            handle("POST", "/analyze", r#"{"dots":"2x400000000"}"#).0,
+            400
+        );
+        assert_eq!(handle("POST", "/analyze", "27x5").0, 400);
+        let (status, body) = handle("POST", "/analyze", "26x26");
+        assert_eq!(status, 400);
+        assert!(body.contains("1300 unset walls"));
+
+        // Over long request lines and headers, and too many headers.
+        for head in [
+            format!("POST /{} HTTP/1.1\r\n\r\n", "a".repeat(MAXIMUM_LINE)),
+            format!(
+                "POST /analyze HTTP/1.1\r\nX: {}\r\n\r\n",
+                "a".repeat(MAXIMUM_LINE)
+            ),
+            format!(
+                "POST /analyze HTTP/1.1\r\n{}\r\n",
+                "X: a\r\n".repeat(MAXIMUM_HEADERS + 1)
+            ),
+            String::from("POST /analyze HTTP/1.1\r\nContent-Length: 3"),
+        ] {
+            assert!(read_request(head.as_bytes()).is_err());
+        }
+        assert_eq!(
+            read_request("POST /analyze HTTP/1.1\r\nContent-Length: 3\r\n\r\n3x3".as_bytes()),
+            Ok((
+                String::from("POST"),
+                String::from("/analyze"),
+                String::from("3x3")
+            ))
+        );
+    }
+
+    #[test]
+    fn limits_solving() {
+        // Too many walls left to solve.
+        let (status, body) = handle("POST", "/solve", "7x7");
+        assert_eq!(status, 400);
+        assert!(body.contains("84 unset walls"));
+
+        // Out of time.
+        let cancel = CancelToken::new();
+        drop(cancel_after(&cancel, Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(10));
+        assert!(!cancel.is_cancelled());
+        let _timer = cancel_after(&cancel, Duration::ZERO);
+        while !cancel.is_cancelled() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            solve_position("4x4", 1, &cancel),
+            Err(String::from(
+                "The position was not solved within 30 seconds."
+            ))
+        );
+
+        // Busy with other solves.
+        let server = Server::new(1);
+        server.solves.store(MAXIMUM_SOLVES, Ordering::SeqCst);
+        assert_eq!(server.handle("POST", "/solve", "3x3").0, 503);
+        server.solves.store(0, Ordering::SeqCst);
+        assert_eq!(server.handle("POST", "/solve", "3x3").0, 200);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::grid::Grid;
+use crate::search::{ordered_moves, CancelToken};
+use crate::wall::Wall;
+
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Exact solving to the end of the game on several threads. The moves at the root are shared out
+// between the threads, which all use one lock-free transposition table. A move is only searched
+// against scores already proven for other moves, so every move that reaches the best score is
+// searched exactly and the result does not depend on the number of threads or their timing.
+// Scores are in boxes from the point of view of the player to move, counting the rest of the game.
+
+const INFINITY: i32 = 1_000_000;
+// Nodes searched between checks of the cancellation token.
+const CHECK_INTERVAL: u64 = 1024;
+// The transposition table has 2^TABLE_BITS entries of 16 bytes.
+const TABLE_BITS: u32 = 20;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Solution {
+    pub score: i32,
+    // Every move reaching the score, in the order the search tries them. The first is played.
+    pub best_moves: Vec<Wall>,
+    pub nodes: u64,
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+pub fn solve(game_grid: &Grid, threads: usize, cancel: &CancelToken) -> Option<Solution> {
+    // None if the search was cancelled before finishing.
+    let moves = ordered_moves(game_grid, None);
+    if moves.is_empty() {
+        return Some(Solution {
+            score: 0,
+            best_moves: Vec::new(),
+            nodes: 1,
+        });
+    }
+    let table = Table::new();
+    let next_move = AtomicUsize::new(0);
+    // The best score proven so far, and the proven score of each root move.
+    let best_score = AtomicI32::new(-INFINITY);
+    let scores = Mutex::new(vec![None; moves.len()]);
+    let nodes = AtomicU64::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                let mut solver = Solver {
+                    grid: game_grid.clone(),
+                    key: position_key(game_grid),
+                    table: &table,
+                    nodes: 0,
+                    cancel,
+                };
+                loop {
+                    let index = next_move.fetch_add(1, Ordering::SeqCst);
+                    if index >= moves.len() || cancel.is_cancelled() {
+                        break;
+                    }
+                    // Searching one below the best score proves any move that equals it.
+                    let alpha = best_score.load(Ordering::SeqCst).saturating_sub(1);
+                    let Some(score) = solver.root_move(&moves[index], alpha) else {
+                        break;
+                    };
+                    if score > alpha {
+                        best_score.fetch_max(score, Ordering::SeqCst);
+                        scores.lock().unwrap()[index] = Some(score);
+                    }
+                }
+                nodes.fetch_add(solver.nodes, Ordering::SeqCst);
+            });
+        }
+    });
+    if cancel.is_cancelled() {
+        return None;
+    }
+
+    let score = best_score.load(Ordering::SeqCst);
+    let scores = scores.into_inner().unwrap();
+    let best_moves = moves
+        .iter()
+        .zip(scores)
+        .filter(|&(_, move_score)| move_score == Some(score))
+        .map(|(wall, _)| *wall)
+        .collect();
+    Some(Solution {
+        score,
+        best_moves,
+        nodes: nodes.load(Ordering::SeqCst),
+    })
+}
+
+pub(crate) fn wall_key(game_grid: &Grid, wall: &Wall) -> u64 {
+    // A fixed pseudo-random number for each wall (splitmix64 of its index).
+    let index = (wall.row * game_grid.column_count() + wall.column) * 2 + wall.is_column as i32;
+    let mut key = (index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+fn position_key(game_grid: &Grid) -> u64 {
+    // The walls set, combined so that setting or clearing a wall updates the key in one step.
+    let mut key = 0;
+    for column in 0..game_grid.column_count() {
+        for row in 0..game_grid.row_count() {
+            for is_column in [false, true] {
+                let wall = Wall {
+                    set: true,
+                    is_column,
+                    column,
+                    row,
+                };
+                if game_grid.is_valid_wall(&wall) && game_grid.get_wall(is_column, column, row).set
+                {
+                    key ^= wall_key(game_grid, &wall);
+                }
+            }
+        }
+    }
+    key
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Entry {
+    score: i32,
+    bound: Bound,
+    // The top half of the best move's key, which is enough to tell the moves of one position apart.
+    best_move: u64,
+}
+
+// Each slot holds the entry and the entry mixed with its position's key. A slot torn by two
+// threads writing at once no longer mixes back to the key and reads as empty.
+struct Table {
+    slots: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl Table {
+    fn new() -> Self {
+        Self {
+            slots: (0..1 << TABLE_BITS)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    fn slot(&self, key: u64) -> &(AtomicU64, AtomicU64) {
+        &self.slots[(key >> (64 - TABLE_BITS)) as usize]
+    }
+
+    fn get(&self, key: u64) -> Option<Entry> {
+        let (check, data) = self.slot(key);
+        let data = data.load(Ordering::Relaxed);
+        if check.load(Ordering::Relaxed) ^ data != key || data == 0 {
+            return None;
+        }
+        let bound = match (data >> 16) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Some(Entry {
+            score: data as u16 as i16 as i32,
+            bound,
+            best_move: data >> 32,
+        })
+    }
+
+    fn set(&self, key: u64, entry: Entry) {
+        let bound = match entry.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let data = (entry.score as i16 as u16 as u64) | (bound << 16) | (entry.best_move << 32);
+        let (check, stored) = self.slot(key);
+        check.store(key ^ data, Ordering::Relaxed);
+        stored.store(data, Ordering::Relaxed);
+    }
+}
+
+struct Solver<'a> {
+    grid: Grid,
+    key: u64,
+    table: &'a Table,
+    nodes: u64,
+    cancel: &'a CancelToken,
+}
+
+impl Solver<'_> {
+    fn root_move(&mut self, wall: &Wall, alpha: i32) -> Option<i32> {
+        // The score of playing the wall, exact if above alpha and otherwise at most alpha.
+        self.play(wall, alpha, INFINITY)
+    }
+
+    fn play(&mut self, wall: &Wall, alpha: i32, beta: i32) -> Option<i32> {
+        let wall_key = wall_key(&self.grid, wall);
+        let outcome = self.grid.make_move(wall);
+        self.key ^= wall_key;
+        let score = if outcome.moves_again {
+            // The same player moves again, so the window is shifted rather than negated.
+            let completed = outcome.completed;
+            self.negamax(alpha - completed, beta - completed)
+                .map(|score| score + completed)
+        } else {
+            self.negamax(-beta, -alpha).map(|score| -score)
+        };
+        self.key ^= wall_key;
+        self.grid.unmake_move(wall);
+        score
+    }
+
+    fn negamax(&mut self, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) && self.cancel.is_cancelled() {
+            return None;
+        }
+        let mut moves = ordered_moves(&self.grid, None);
+        if moves.is_empty() {
+            return Some(0);
+        }
+
+        let entry = self.table.get(self.key);
+        if let Some(entry) = entry {
+            match entry.bound {
+                Bound::Exact => return Some(entry.score),
+                Bound::Lower => alpha = alpha.max(entry.score),
+                Bound::Upper => beta = beta.min(entry.score),
+            }
+            if alpha >= beta {
+                return Some(entry.score);
+            }
+            let stored = entry.best_move;
+            if let Some(position) = moves
+                .iter()
+                .position(|wall| wall_key(&self.grid, wall) >> 32 == stored)
+            {
+                let wall = moves.remove(position);
+                moves.insert(0, wall);
+            }
+        }
+
+        let mut best = -INFINITY;
+        let mut best_move = moves[0];
+        for wall in &moves {
+            let score = self.play(wall, alpha.max(best), beta)?;
+            if score > best {
+                best = score;
+                best_move = *wall;
+            }
+            if best >= beta {
+                break;
+            }
+        }
+        let bound = if best <= alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.set(
+            self.key,
+            Entry {
+                score: best,
+                bound,
+                best_move: wall_key(&self.grid, &best_move) >> 32,
+            },
+        );
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn full_search(game_grid: &mut Grid) -> i32 {
+        // The exact score by trying every move, without pruning.
+        let moves: Vec<Wall> = game_grid.legal_moves().collect();
+        if moves.is_empty() {
+            return 0;
+        }
+        let mut best = -INFINITY;
+        for wall in moves {
+            let outcome = game_grid.make_move(&wall);
+            let score = if outcome.moves_again {
+                outcome.completed + full_search(game_grid)
+            } else {
+                -full_search(game_grid)
+            };
+            game_grid.unmake_move(&wall);
+            best = best.max(score);
+        }
+        best
+    }
+
+    fn random_position(rng: &mut StdRng, column_count: i32, row_count: i32, moves: usize) -> Grid {
+        let mut game_grid = Grid::new(column_count, row_count);
+        let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+        walls.shuffle(rng);
+        for wall in &walls[..moves] {
+            game_grid.make_move(wall);
+        }
+        game_grid
+    }
+
+    #[test]
+    fn matches_a_full_search_of_small_positions() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..10 {
+            let mut game_grid = random_position(&mut rng, 4, 3, 10);
+            let solution = solve(&game_grid, 2, &CancelToken::new()).unwrap();
+            assert_eq!(solution.score, full_search(&mut game_grid));
+            for wall in &solution.best_moves {
+                let outcome = game_grid.make_move(wall);
+                let mut score = full_search(&mut game_grid);
+                if !outcome.moves_again {
+                    score = -score;
+                }
+                game_grid.unmake_move(wall);
+                assert_eq!(score + outcome.completed, solution.score);
+            }
+        }
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_solution() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..3 {
+            let game_grid = random_position(&mut rng, 4, 4, 10);
+            let single = solve(&game_grid, 1, &CancelToken::new()).unwrap();
+            let several = solve(&game_grid, 4, &CancelToken::new()).unwrap();
+            assert_eq!(single.score, several.score);
+            assert_eq!(single.best_moves, several.best_moves);
+        }
+    }
+
+    #[test]
+    fn cancelled_solves_return_nothing() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert_eq!(solve(&Grid::new(5, 5), 2, &cancel), None);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::wall::Wall;
 #[derive(PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SquareWalls {
     pub top: Wall,
     pub right: Wall,
@@ -22,7 +23,7 @@ impl SquareWalls {
         if self.left.set == set {
             walls.push(self.left)
         };
-        return walls;
+        walls
     }
 
     pub fn get_first_wall(&self, set: bool) -> Option<Wall> {
@@ -38,6 +39,6 @@ impl SquareWalls {
         if self.left.set == set {
             return Some(self.left);
         };
-        return None;
+        None
     }
 }
@@ -0,0 +1,509 @@
+use crate::engine::parse_dots;
+use crate::grid::Grid;
+use crate::wall::Wall;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+// Perfect play for small boards. A tablebase holds the score of the rest of the game, for the
+// player to move, of every position of one grid size. Positions are the walls set, as a bitmask
+// over the grid's walls in legal move order, and positions that are reflections or rotations of
+// each other share one entry kept under the smallest of their masks.
+//
+// Files start with "DBTB", the format version and the grid size in dots, followed by one four bit
+// value per canonical position in increasing mask order, two to a byte with the first in the low
+// bits. The value is half the sum of the score and the boxes still open, which is never negative
+// and never more than the boxes open because each box changes the score by one.
+//
+// Tablebases in the directory named by DOTS_AND_BOXES_TABLEBASES are installed on first use, and
+// the advisor plays from an installed tablebase when one covers the position.
+
+const TABLEBASE_USAGE: &str = "Usage: dots_and_boxes tablebase --dots COLUMNSxROWS --output FILE";
+const MAGIC: &[u8; 4] = b"DBTB";
+const VERSION: u8 = 1;
+// The most walls a tablebase can cover: 3x3 boxes, with 2^24 positions before symmetry reduction.
+pub const MAXIMUM_WALLS: usize = 24;
+const EXTENSION: &str = "dbtb";
+
+pub struct Tablebase {
+    column_count: i32,
+    row_count: i32,
+    // The grid's walls, indexed by their bit in a position.
+    walls: Vec<Wall>,
+    // For each symmetry and each byte of a position, the bits that byte maps to.
+    symmetries: Vec<Vec<[u32; 256]>>,
+    // For each block of 64 positions, which are canonical and how many canonical positions come
+    // before the block.
+    canonical: Vec<u64>,
+    ranks: Vec<u32>,
+    values: Vec<u8>,
+}
+
+pub(crate) fn symmetries(column_count: i32, row_count: i32) -> Vec<usize> {
+    // The reflections and rotations of a grid, as arguments to transform. Only square grids can be
+    // transposed.
+    if column_count == row_count {
+        (0..8).collect()
+    } else {
+        vec![0, 2, 4, 6]
+    }
+}
+
+pub(crate) fn transform(wall: &Wall, symmetry: usize, column_count: i32, row_count: i32) -> Wall {
+    // Bit 0 of the symmetry transposes the grid (square grids only), bit 1 flips the columns and
+    // bit 2 flips the rows. The wall's dots are transformed and the wall rebuilt from them.
+    let (x, y) = (wall.column, wall.row);
+    let mut ends = if wall.is_column {
+        [(x, y), (x, y + 1)]
+    } else {
+        [(x, y), (x + 1, y)]
+    };
+    for (x, y) in &mut ends {
+        if symmetry & 1 != 0 {
+            (*x, *y) = (*y, *x);
+        }
+        if symmetry & 2 != 0 {
+            *x = column_count - 1 - *x;
+        }
+        if symmetry & 4 != 0 {
+            *y = row_count - 1 - *y;
+        }
+    }
+    Wall {
+        set: false,
+        is_column: ends[0].0 == ends[1].0,
+        column: ends[0].0.min(ends[1].0),
+        row: ends[0].1.min(ends[1].1),
+    }
+}
+
+impl Tablebase {
+    fn empty(column_count: i32, row_count: i32) -> Result<Self, String> {
+        // A tablebase with its walls and symmetries but no values.
+        let walls: Vec<Wall> = Grid::new(column_count, row_count).legal_moves().collect();
+        if walls.len() > MAXIMUM_WALLS {
+            return Err(format!(
+                "A {column_count}x{row_count} grid has {} walls, more than the {MAXIMUM_WALLS} a \
+tablebase can cover.",
+                walls.len()
+            ));
+        }
+        let index: HashMap<Wall, usize> = walls
+            .iter()
+            .enumerate()
+            .map(|(bit, wall)| (*wall, bit))
+            .collect();
+        let symmetries = symmetries(column_count, row_count)
+            .into_iter()
+            .map(|symmetry| {
+                let image: Vec<usize> = walls
+                    .iter()
+                    .map(|wall| index[&transform(wall, symmetry, column_count, row_count)])
+                    .collect();
+                (0..walls.len().div_ceil(8))
+                    .map(|byte| {
+                        let mut table = [0; 256];
+                        for (value, bits) in table.iter_mut().enumerate() {
+                            for bit in 0..8 {
+                                if value & (1 << bit) != 0 && byte * 8 + bit < walls.len() {
+                                    *bits |= 1 << image[byte * 8 + bit];
+                                }
+                            }
+                        }
+                        table
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut tablebase = Self {
+            column_count,
+            row_count,
+            walls,
+            symmetries,
+            canonical: Vec::new(),
+            ranks: Vec::new(),
+            values: Vec::new(),
+        };
+
+        let position_count = tablebase.position_count();
+        tablebase.canonical = vec![0; position_count.div_ceil(64)];
+        tablebase.ranks = vec![0; position_count.div_ceil(64)];
+        let mut rank = 0;
+        for position in 0..position_count as u32 {
+            let block = position as usize / 64;
+            if position % 64 == 0 {
+                tablebase.ranks[block] = rank;
+            }
+            if tablebase.canonical_position(position) == position {
+                tablebase.canonical[block] |= 1 << (position % 64);
+                rank += 1;
+            }
+        }
+        Ok(tablebase)
+    }
+
+    pub fn generate(column_count: i32, row_count: i32) -> Result<Self, String> {
+        // Score every position from the full grid back to the empty one. Setting a wall only adds
+        // to a position's mask, so every position after a move has been scored before it.
+        let mut tablebase = Self::empty(column_count, row_count)?;
+        let squares = tablebase.square_masks();
+        // The squares on either side of each wall.
+        let wall_squares: Vec<Vec<u32>> = (0..tablebase.walls.len())
+            .map(|bit| {
+                squares
+                    .iter()
+                    .copied()
+                    .filter(|square| square & (1 << bit) != 0)
+                    .collect()
+            })
+            .collect();
+
+        let position_count = tablebase.position_count();
+        let mut scores = vec![0i8; position_count];
+        for position in (0..position_count as u32).rev() {
+            let mut best = None;
+            for (bit, adjacent) in wall_squares.iter().enumerate() {
+                if position & (1 << bit) != 0 {
+                    continue;
+                }
+                let next = position | (1 << bit);
+                let completed = adjacent
+                    .iter()
+                    .filter(|&&square| next & square == square)
+                    .count() as i8;
+                let score = if completed > 0 {
+                    completed + scores[next as usize]
+                } else {
+                    -scores[next as usize]
+                };
+                best = Some(best.map_or(score, |best: i8| best.max(score)));
+            }
+            scores[position as usize] = best.unwrap_or(0);
+        }
+
+        tablebase.values = vec![0; tablebase.canonical_count().div_ceil(2)];
+        let mut rank = 0;
+        for position in 0..position_count as u32 {
+            if tablebase.is_canonical(position) {
+                let open = squares
+                    .iter()
+                    .filter(|&&square| position & square != square)
+                    .count() as i32;
+                let value = ((scores[position as usize] as i32 + open) / 2) as u8;
+                tablebase.values[rank / 2] |= value << (rank % 2 * 4);
+                rank += 1;
+            }
+        }
+        Ok(tablebase)
+    }
+
+    pub fn column_count(&self) -> i32 {
+        self.column_count
+    }
+
+    pub fn row_count(&self) -> i32 {
+        self.row_count
+    }
+
+    pub fn position_count(&self) -> usize {
+        1 << self.walls.len()
+    }
+
+    pub fn canonical_count(&self) -> usize {
+        self.ranks.last().copied().unwrap_or(0) as usize
+            + self
+                .canonical
+                .last()
+                .map_or(0, |bits| bits.count_ones() as usize)
+    }
+
+    fn square_masks(&self) -> Vec<u32> {
+        // Each square's four walls as a mask.
+        let game_grid = Grid::new(self.column_count, self.row_count);
+        let mut squares = Vec::new();
+        for column in 0..self.column_count - 1 {
+            for row in 0..self.row_count - 1 {
+                let square_walls = game_grid.get_square_walls(column, row);
+                let mut square = 0;
+                for wall in [
+                    square_walls.top,
+                    square_walls.right,
+                    square_walls.bottom,
+                    square_walls.left,
+                ] {
+                    let bit = self
+                        .walls
+                        .iter()
+                        .position(|other| Wall { set: false, ..wall } == *other)
+                        .unwrap();
+                    square |= 1 << bit;
+                }
+                squares.push(square);
+            }
+        }
+        squares
+    }
+
+    fn canonical_position(&self, position: u32) -> u32 {
+        // The smallest mask of the position's reflections and rotations.
+        self.symmetries
+            .iter()
+            .map(|tables| {
+                tables.iter().enumerate().fold(0, |image, (byte, table)| {
+                    image | table[(position >> (byte * 8)) as usize & 0xff]
+                })
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn is_canonical(&self, position: u32) -> bool {
+        self.canonical[position as usize / 64] & (1 << (position % 64)) != 0
+    }
+
+    fn position(&self, game_grid: &Grid) -> Option<u32> {
+        if game_grid.column_count() != self.column_count || game_grid.row_count() != self.row_count
+        {
+            return None;
+        }
+        let mut position = 0;
+        for (bit, wall) in self.walls.iter().enumerate() {
+            if game_grid
+                .get_wall(wall.is_column, wall.column, wall.row)
+                .set
+            {
+                position |= 1 << bit;
+            }
+        }
+        Some(position)
+    }
+
+    pub fn score(&self, game_grid: &Grid) -> Option<i32> {
+        // The score of the rest of the game for the player to move, or None if the tablebase is for
+        // another grid size.
+        let position = self.canonical_position(self.position(game_grid)?);
+        let block = position as usize / 64;
+        let below = self.canonical[block] & ((1 << (position % 64)) - 1);
+        let rank = self.ranks[block] as usize + below.count_ones() as usize;
+        let value = (self.values[rank / 2] >> (rank % 2 * 4)) & 0xf;
+        let mut open = 0;
+        for column in 0..self.column_count - 1 {
+            for row in 0..self.row_count - 1 {
+                if game_grid.get_wall_count(column, row) < 4 {
+                    open += 1;
+                }
+            }
+        }
+        Some(value as i32 * 2 - open)
+    }
+
+    pub fn best_moves(&self, game_grid: &Grid) -> HashSet<Wall> {
+        // Every move reaching the score of the position, which must be of the tablebase's size.
+        let mut next_grid = game_grid.clone();
+        let scores: Vec<(Wall, i32)> = game_grid
+            .legal_moves()
+            .map(|wall| {
+                let outcome = next_grid.make_move(&wall);
+                let next_score = self.score(&next_grid).unwrap();
+                next_grid.unmake_move(&wall);
+                let score = if outcome.moves_again {
+                    outcome.completed + next_score
+                } else {
+                    -next_score
+                };
+                (wall, score)
+            })
+            .collect();
+        let best = scores.iter().map(|&(_, score)| score).max();
+        scores
+            .into_iter()
+            .filter(|&(_, score)| Some(score) == best)
+            .map(|(wall, _)| wall)
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.column_count as u8);
+        bytes.push(self.row_count as u8);
+        bytes.extend_from_slice(&self.values);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 7 || &bytes[..4] != MAGIC {
+            return Err(String::from("The file is not a tablebase."));
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("Tablebase version {} is not supported.", bytes[4]));
+        }
+        let (column_count, row_count) = (bytes[5] as i32, bytes[6] as i32);
+        if !(2..=26).contains(&column_count) || !(2..=26).contains(&row_count) {
+            return Err(format!(
+                "The tablebase has an invalid grid size of {column_count}x{row_count}."
+            ));
+        }
+        let mut tablebase = Self::empty(column_count, row_count)?;
+        let values = &bytes[7..];
+        if values.len() != tablebase.canonical_count().div_ceil(2) {
+            return Err(String::from("The tablebase is truncated."));
+        }
+        tablebase.values = values.to_vec();
+        Ok(tablebase)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path)
+            .map_err(|error| format!("Unable to read {}: {error}.", path.display()))?;
+        Self::from_bytes(&bytes).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes())
+            .map_err(|error| format!("Unable to write {}: {error}.", path.display()))
+    }
+}
+
+fn installed() -> &'static RwLock<Vec<Arc<Tablebase>>> {
+    static INSTALLED: OnceLock<RwLock<Vec<Arc<Tablebase>>>> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        let mut tablebases = Vec::new();
+        if let Some(directory) = env::var_os("DOTS_AND_BOXES_TABLEBASES") {
+            let entries = fs::read_dir(&directory).into_iter().flatten().flatten();
+            for entry in entries {
+                let path = entry.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == EXTENSION)
+                {
+                    match Tablebase::read(&path) {
+                        Ok(tablebase) => tablebases.push(Arc::new(tablebase)),
+                        Err(error) => eprintln!("{error}"),
+                    }
+                }
+            }
+        }
+        RwLock::new(tablebases)
+    })
+}
+
+pub fn install(tablebase: Tablebase) {
+    // Replaces any installed tablebase for the same grid size.
+    let mut tablebases = installed().write().unwrap();
+    tablebases.retain(|other| {
+        (other.column_count, other.row_count) != (tablebase.column_count, tablebase.row_count)
+    });
+    tablebases.push(Arc::new(tablebase));
+}
+
+pub fn find(column_count: i32, row_count: i32) -> Option<Arc<Tablebase>> {
+    installed()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|tablebase| {
+            (tablebase.column_count, tablebase.row_count) == (column_count, row_count)
+        })
+        .cloned()
+}
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    let (dots, output) = match arguments {
+        [dots_flag, dots, output_flag, output]
+            if dots_flag == "--dots" && output_flag == "--output" =>
+        {
+            (dots, output)
+        }
+        [output_flag, output, dots_flag, dots]
+            if dots_flag == "--dots" && output_flag == "--output" =>
+        {
+            (dots, output)
+        }
+        _ => return Err(String::from(TABLEBASE_USAGE)),
+    };
+    let (column_count, row_count) =
+        parse_dots(dots).map_err(|error| format!("{error}\n{TABLEBASE_USAGE}"))?;
+    let tablebase = Tablebase::generate(column_count, row_count)?;
+    tablebase.write(Path::new(output))?;
+    println!(
+        "Wrote {} positions ({} before symmetry reduction) to {output}.",
+        tablebase.canonical_count(),
+        tablebase.position_count()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::CancelToken;
+    use crate::solver::solve;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn random_position(rng: &mut StdRng, column_count: i32, row_count: i32, moves: usize) -> Grid {
+        let mut game_grid = Grid::new(column_count, row_count);
+        let mut walls: Vec<Wall> = game_grid.legal_moves().collect();
+        walls.shuffle(rng);
+        for wall in &walls[..moves] {
+            game_grid.make_move(wall);
+        }
+        game_grid
+    }
+
+    #[test]
+    fn scores_match_the_solver() {
+        let tablebase = Tablebase::generate(4, 3).unwrap();
+        assert_eq!(tablebase.position_count(), 1 << 17);
+        assert!(tablebase.canonical_count() < tablebase.position_count() / 3);
+        let mut rng = StdRng::seed_from_u64(3);
+        for moves in [6, 9, 12, 16, 17] {
+            let game_grid = random_position(&mut rng, 4, 3, moves);
+            let solution = solve(&game_grid, 1, &CancelToken::new()).unwrap();
+            assert_eq!(tablebase.score(&game_grid), Some(solution.score));
+            assert_eq!(
+                tablebase.best_moves(&game_grid),
+                solution.best_moves.into_iter().collect()
+            );
+        }
+        assert_eq!(tablebase.score(&Grid::new(3, 4)), None);
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let tablebase = Tablebase::generate(3, 3).unwrap();
+        let bytes = tablebase.to_bytes();
+        let read = Tablebase::from_bytes(&bytes).unwrap();
+        let mut rng = StdRng::seed_from_u64(9);
+        for moves in 0..=12 {
+            let game_grid = random_position(&mut rng, 3, 3, moves);
+            assert_eq!(read.score(&game_grid), tablebase.score(&game_grid));
+        }
+        assert!(Tablebase::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Tablebase::from_bytes(b"DBTB\x02\x03\x03").is_err());
+        assert!(Tablebase::from_bytes(b"DBTB\x01\x00\x00").is_err());
+        assert!(Tablebase::from_bytes(b"DBTB\x01\x03\x1b").is_err());
+        assert!(Tablebase::generate(5, 5).is_err());
+    }
+
+    #[test]
+    fn the_advisor_plays_from_installed_tablebases() {
+        // No other test uses this grid size, so installing a tablebase for it changes nothing else.
+        install(Tablebase::generate(5, 2).unwrap());
+        let mut rng = StdRng::seed_from_u64(1);
+        for moves in [2, 5, 8] {
+            let game_grid = random_position(&mut rng, 5, 2, moves);
+            let solution = solve(&game_grid, 1, &CancelToken::new()).unwrap();
+            assert_eq!(game_grid.tablebase_score(), Some(solution.score));
+            assert_eq!(
+                game_grid.get_optimal_moves(),
+                solution.best_moves.into_iter().collect()
+            );
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use flo_canvas::Color;
+use std::fs;
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub background_fill: Color,
+    pub square_fill: [Color; 5],
+    pub dot_fill: Color,
+    pub line_stroke: Color,
+    pub optimal_move_stroke: Color,
+    pub cursor_stroke: Color,
+    // Fill of completed squares by the player that completed them.
+    pub player_fill: [Color; 2],
+    // Marks on unset walls by what the move does: capture, double-cross, safe, sacrifice and loony.
+    pub move_class_stroke: [Color; 5],
+    // The heatmap gradient from the best moves to the move losing the most boxes.
+    pub heatmap_stroke: [Color; 2],
+    // Thickness of set walls and of advisor highlights (in squares).
+    pub line_width: f32,
+    pub optimal_move_width: f32,
+    // Hatch squares by wall count so they can be told apart without color.
+    pub square_patterns: bool,
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            name: String::from("light"),
+            background_fill: Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+            square_fill: [
+                Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+                Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+                Color::Rgba(1.0, 1.0, 0.0, 1.0), // Yellow
+                Color::Rgba(0.0, 0.0, 1.0, 1.0), // Blue
+                Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+            ],
+            dot_fill: Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+            line_stroke: Color::Rgba(0.392, 0.392, 0.392, 1.0), // Gray
+            optimal_move_stroke: Color::Rgba(0.0, 1.0, 0.0, 1.0), // Green
+            cursor_stroke: Color::Rgba(1.0, 0.0, 0.0, 1.0), // Red
+            player_fill: [
+                Color::Rgba(1.0, 0.6, 0.6, 1.0), // Pink
+                Color::Rgba(0.6, 0.8, 1.0, 1.0), // Light blue
+            ],
+            move_class_stroke: [
+                Color::Rgba(0.0, 0.4, 1.0, 1.0),    // Blue
+                Color::Rgba(0.6, 0.0, 0.8, 1.0),    // Purple
+                Color::Rgba(0.75, 0.75, 0.75, 1.0), // Light gray
+                Color::Rgba(1.0, 0.6, 0.0, 1.0),    // Orange
+                Color::Rgba(0.9, 0.0, 0.0, 1.0),    // Red
+            ],
+            heatmap_stroke: [
+                Color::Rgba(0.0, 0.8, 0.0, 1.0), // Green
+                Color::Rgba(0.9, 0.0, 0.0, 1.0), // Red
+            ],
+            line_width: 0.2,
+            optimal_move_width: 0.3,
+            square_patterns: false,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            name: String::from("dark"),
+            background_fill: Color::Rgba(0.118, 0.118, 0.118, 1.0), // Charcoal
+            square_fill: [
+                Color::Rgba(0.118, 0.118, 0.118, 1.0), // Charcoal
+                Color::Rgba(0.118, 0.118, 0.118, 1.0), // Charcoal
+                Color::Rgba(0.541, 0.478, 0.0, 1.0),   // Olive
+                Color::Rgba(0.122, 0.310, 0.627, 1.0), // Navy
+                Color::Rgba(0.816, 0.816, 0.816, 1.0), // Light gray
+            ],
+            dot_fill: Color::Rgba(0.878, 0.878, 0.878, 1.0), // Light gray
+            line_stroke: Color::Rgba(0.627, 0.627, 0.627, 1.0), // Gray
+            optimal_move_stroke: Color::Rgba(0.247, 0.812, 0.247, 1.0), // Green
+            cursor_stroke: Color::Rgba(1.0, 0.314, 0.314, 1.0), // Red
+            player_fill: [
+                Color::Rgba(0.6, 0.2, 0.2, 1.0), // Maroon
+                Color::Rgba(0.2, 0.3, 0.6, 1.0), // Navy
+            ],
+            move_class_stroke: [
+                Color::Rgba(0.361, 0.612, 1.0, 1.0),   // Light blue
+                Color::Rgba(0.769, 0.478, 1.0, 1.0),   // Lavender
+                Color::Rgba(0.376, 0.376, 0.376, 1.0), // Dark gray
+                Color::Rgba(1.0, 0.663, 0.247, 1.0),   // Orange
+                Color::Rgba(1.0, 0.314, 0.314, 1.0),   // Red
+            ],
+            heatmap_stroke: [
+                Color::Rgba(0.247, 0.812, 0.247, 1.0), // Green
+                Color::Rgba(1.0, 0.314, 0.314, 1.0),   // Red
+            ],
+            line_width: 0.2,
+            optimal_move_width: 0.3,
+            square_patterns: false,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            name: String::from("high-contrast"),
+            background_fill: Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+            square_fill: [
+                Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+                Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+                Color::Rgba(1.0, 1.0, 0.0, 1.0), // Yellow
+                Color::Rgba(0.0, 1.0, 1.0, 1.0), // Cyan
+                Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+            ],
+            dot_fill: Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+            line_stroke: Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+            optimal_move_stroke: Color::Rgba(1.0, 0.0, 1.0, 1.0), // Magenta
+            cursor_stroke: Color::Rgba(1.0, 0.5, 0.0, 1.0), // Orange
+            player_fill: [
+                Color::Rgba(1.0, 0.0, 0.0, 1.0), // Red
+                Color::Rgba(0.0, 0.0, 1.0, 1.0), // Blue
+            ],
+            move_class_stroke: [
+                Color::Rgba(0.0, 1.0, 1.0, 1.0), // Cyan
+                Color::Rgba(0.0, 1.0, 0.0, 1.0), // Green
+                Color::Rgba(0.5, 0.5, 0.5, 1.0), // Gray
+                Color::Rgba(1.0, 1.0, 0.0, 1.0), // Yellow
+                Color::Rgba(1.0, 0.0, 0.0, 1.0), // Red
+            ],
+            heatmap_stroke: [
+                Color::Rgba(0.0, 1.0, 0.0, 1.0), // Green
+                Color::Rgba(1.0, 0.0, 0.0, 1.0), // Red
+            ],
+            line_width: 0.2,
+            optimal_move_width: 0.35,
+            square_patterns: true,
+        }
+    }
+
+    pub fn colorblind() -> Self {
+        // Okabe-Ito palette.
+        Self {
+            name: String::from("colorblind"),
+            background_fill: Color::Rgba(1.0, 1.0, 1.0, 1.0), // White
+            square_fill: [
+                Color::Rgba(1.0, 1.0, 1.0, 1.0),       // White
+                Color::Rgba(1.0, 1.0, 1.0, 1.0),       // White
+                Color::Rgba(0.941, 0.894, 0.259, 1.0), // Yellow
+                Color::Rgba(0.337, 0.706, 0.914, 1.0), // Sky blue
+                Color::Rgba(0.0, 0.0, 0.0, 1.0),       // Black
+            ],
+            dot_fill: Color::Rgba(0.0, 0.0, 0.0, 1.0), // Black
+            line_stroke: Color::Rgba(0.392, 0.392, 0.392, 1.0), // Gray
+            optimal_move_stroke: Color::Rgba(0.835, 0.369, 0.0, 1.0), // Vermillion
+            cursor_stroke: Color::Rgba(0.0, 0.447, 0.698, 1.0), // Blue
+            player_fill: [
+                Color::Rgba(0.902, 0.624, 0.0, 1.0), // Orange
+                Color::Rgba(0.0, 0.620, 0.451, 1.0), // Bluish green
+            ],
+            move_class_stroke: [
+                Color::Rgba(0.0, 0.447, 0.698, 1.0),   // Blue
+                Color::Rgba(0.0, 0.620, 0.451, 1.0),   // Bluish green
+                Color::Rgba(0.337, 0.706, 0.914, 1.0), // Sky blue
+                Color::Rgba(0.902, 0.624, 0.0, 1.0),   // Orange
+                Color::Rgba(0.8, 0.475, 0.655, 1.0),   // Reddish purple
+            ],
+            heatmap_stroke: [
+                Color::Rgba(0.0, 0.447, 0.698, 1.0), // Blue
+                Color::Rgba(0.835, 0.369, 0.0, 1.0), // Vermillion
+            ],
+            line_width: 0.2,
+            optimal_move_width: 0.35,
+            square_patterns: true,
+        }
+    }
+
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::light(),
+            Theme::dark(),
+            Theme::high_contrast(),
+            Theme::colorblind(),
+        ]
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::built_in()
+            .into_iter()
+            .find(|theme| theme.name == name)
+    }
+
+    pub fn from_argument(argument: &str) -> Result<Theme, String> {
+        // A built-in theme name or the path of a theme file.
+        match Theme::by_name(argument) {
+            Some(theme) => Ok(theme),
+            None => Theme::load(argument),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Theme, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read theme file {path}: {error}."))?;
+        Theme::parse(&contents).map_err(|error| format!("{path}: {error}"))
+    }
+
+    pub fn parse(contents: &str) -> Result<Theme, String> {
+        // Theme files consist of "key = value" lines. Keys that are not present are taken from the
+        // base theme (light unless a "base" key names another built-in theme). The base replaces
+        // every key, so it has to come first.
+        let mut theme = Theme::light();
+        theme.name = String::from("custom");
+        let mut first_key = true;
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("Line {line_number}: expected \"key = value\".")),
+            };
+            let error = |message: String| format!("Line {line_number}: {message}");
+            if key == "base" && !first_key {
+                return Err(error(String::from(
+                    "base has to come before the other keys.",
+                )));
+            }
+            first_key = false;
+            match key {
+                "base" => {
+                    let name = theme.name;
+                    theme = Theme::by_name(value)
+                        .ok_or_else(|| error(format!("unknown base theme \"{value}\".")))?;
+                    theme.name = name;
+                }
+                "name" => theme.name = String::from(value),
+                "background" => theme.background_fill = parse_color(value).map_err(error)?,
+                "square_fill" => {
+                    theme.square_fill =
+                        parse_colors(value, "square_fill", "one per wall count").map_err(error)?
+                }
+                "dot" => theme.dot_fill = parse_color(value).map_err(error)?,
+                "line" => theme.line_stroke = parse_color(value).map_err(error)?,
+                "optimal_move" => theme.optimal_move_stroke = parse_color(value).map_err(error)?,
+                "cursor" => theme.cursor_stroke = parse_color(value).map_err(error)?,
+                "player_fill" => {
+                    theme.player_fill =
+                        parse_colors(value, "player_fill", "one per player").map_err(error)?
+                }
+                "move_class" => {
+                    theme.move_class_stroke = parse_colors(
+                        value,
+                        "move_class",
+                        "capture, double-cross, safe, sacrifice and loony",
+                    )
+                    .map_err(error)?
+                }
+                "heatmap" => {
+                    theme.heatmap_stroke =
+                        parse_colors(value, "heatmap", "best and worst move").map_err(error)?
+                }
+                "line_width" => theme.line_width = parse_width(value).map_err(error)?,
+                "optimal_move_width" => {
+                    theme.optimal_move_width = parse_width(value).map_err(error)?
+                }
+                "square_patterns" => {
+                    theme.square_patterns = value
+                        .parse()
+                        .map_err(|_| error(format!("expected true or false, found \"{value}\".")))?
+                }
+                _ => return Err(error(format!("unknown key \"{key}\"."))),
+            }
+        }
+        // Highlights are drawn over walls, so they would be hidden if they were not wider.
+        if theme.optimal_move_width <= theme.line_width {
+            return Err(format!(
+                "optimal_move_width ({}) has to be wider than line_width ({}).",
+                theme.optimal_move_width, theme.line_width
+            ));
+        }
+        Ok(theme)
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    // Colors are written as #rrggbb or #rrggbbaa.
+    let hex = value
+        .strip_prefix('#')
+        .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+        .ok_or_else(|| format!("expected a color like #rrggbb, found \"{value}\"."))?;
+    let mut components = [1.0; 4];
+    for (index, component) in components.iter_mut().enumerate().take(hex.len() / 2) {
+        let byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+            .map_err(|_| format!("invalid hexadecimal in color \"{value}\"."))?;
+        *component = byte as f32 / 255.0;
+    }
+    Ok(Color::Rgba(
+        components[0],
+        components[1],
+        components[2],
+        components[3],
+    ))
+}
+
+fn parse_colors<const N: usize>(
+    value: &str,
+    key: &str,
+    description: &str,
+) -> Result<[Color; N], String> {
+    // A list of colors separated by spaces, one for each of the N things the key colors.
+    let colors = value
+        .split_whitespace()
+        .map(parse_color)
+        .collect::<Result<Vec<Color>, String>>()?;
+    colors.try_into().map_err(|colors: Vec<Color>| {
+        format!(
+            "{key} needs {N} colors ({description}), found {}.",
+            colors.len()
+        )
+    })
+}
+
+fn parse_width(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(width) if width > 0.0 && width < 1.0 => Ok(width),
+        _ => Err(format!(
+            "expected a width between 0 and 1 squares, found \"{value}\"."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components(color: Color) -> (f32, f32, f32, f32) {
+        color.to_rgba_components()
+    }
+
+    #[test]
+    fn parses_theme_files() {
+        let theme = Theme::parse(include_str!("../themes/solarized.theme")).unwrap();
+        assert_eq!(theme.name, "solarized");
+        assert_eq!(
+            components(theme.background_fill),
+            components(parse_color("#fdf6e3").unwrap())
+        );
+        assert_eq!(theme.optimal_move_width, 0.3);
+        // Keys left out come from the base theme.
+        assert_eq!(theme.line_width, Theme::light().line_width);
+
+        let theme = Theme::parse("base = dark\nline_width = 0.1\n").unwrap();
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.line_width, 0.1);
+        assert_eq!(
+            components(theme.dot_fill),
+            components(Theme::dark().dot_fill)
+        );
+        let theme = Theme::parse("# Comment\n\nbase = high-contrast\n").unwrap();
+        assert!(theme.square_patterns);
+        assert_eq!(
+            components(parse_color("#ff000080").unwrap()),
+            (1.0, 0.0, 0.0, 128.0 / 255.0)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_theme_files() {
+        for (contents, error) in [
+            ("dot = #000000\nbase = dark\n", "Line 2: base has"),
+            ("base = sepia\n", "Line 1: unknown base theme"),
+            ("dot #000000\n", "Line 1: expected \"key = value\""),
+            ("dots = #000000\n", "Line 1: unknown key"),
+            ("dot = black\n", "Line 1: expected a color"),
+            ("dot = #00000g\n", "Line 1: invalid hexadecimal"),
+            (
+                "player_fill = #000000\n",
+                "Line 1: player_fill needs 2 colors",
+            ),
+            (
+                "square_fill = #000000\n",
+                "Line 1: square_fill needs 5 colors",
+            ),
+            ("line_width = 1.5\n", "Line 1: expected a width"),
+            ("square_patterns = yes\n", "Line 1: expected true or false"),
+            (
+                "optimal_move_width = 0.1\n",
+                "optimal_move_width (0.1) has to be wider",
+            ),
+            (
+                "line_width = 0.3\n",
+                "optimal_move_width (0.3) has to be wider",
+            ),
+        ] {
+            let message = Theme::parse(contents).err().unwrap();
+            assert!(message.starts_with(error), "{message}");
+        }
+    }
+
+    #[test]
+    fn highlights_are_wider_than_walls() {
+        for theme in Theme::built_in() {
+            assert!(
+                theme.optimal_move_width > theme.line_width,
+                "{}",
+                theme.name
+            );
+        }
+    }
+}
@@ -0,0 +1,789 @@
+use crate::engine::{advisor_move, parse_dots, ExternalEngine};
+use crate::game::Game;
+use crate::grid::Grid;
+use crate::json::quote;
+use crate::observer::{Broadcast, JsonLog, Observers};
+use crate::search::{search, CancelToken, SearchLimits};
+use crate::wall::Wall;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::fmt::Write;
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+const TOURNAMENT_USAGE: &str = "Usage: dots_and_boxes tournament [--format round-robin|gauntlet] \
+[--sizes 4x4,6x6] [--openings COUNT] [--opening-moves COUNT] [--seed NUMBER] \
+[--move-time MILLISECONDS] [--output FILE] [--log FILE] [--spectators PORT] STRATEGY STRATEGY...
+Strategies are advisor, heuristics, greedy, random, search:<depth> or engine:<command>. In a gauntlet the first strategy plays \
+all of the others.";
+
+#[derive(Clone, PartialEq)]
+pub enum Strategy {
+    // The first of the advisor's moves, as played by the engine mode.
+    Advisor,
+    // The first of the moves found by the advisor's heuristics alone, without the opening books,
+    // tablebases or long chain parity narrowing the safe moves.
+    Heuristics,
+    // Complete a box if possible, otherwise a random move that does not give one away.
+    Greedy,
+    Random,
+    // The alpha-beta search to the given depth in plies, stopping early at the move time.
+    Search(i32),
+    // An external engine speaking the engine protocol, started with the given command.
+    Engine(String),
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Advisor => write!(f, "advisor"),
+            Strategy::Heuristics => write!(f, "heuristics"),
+            Strategy::Greedy => write!(f, "greedy"),
+            Strategy::Random => write!(f, "random"),
+            Strategy::Search(depth) => write!(f, "search:{depth}"),
+            Strategy::Engine(command) => write!(f, "engine:{command}"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "advisor" => Ok(Strategy::Advisor),
+            "heuristics" => Ok(Strategy::Heuristics),
+            "greedy" => Ok(Strategy::Greedy),
+            "random" => Ok(Strategy::Random),
+            _ => {
+                if let Some(depth) = text.strip_prefix("search:") {
+                    return match depth.parse() {
+                        Ok(depth) if depth > 0 => Ok(Strategy::Search(depth)),
+                        _ => Err(format!("Invalid search depth \"{depth}\".")),
+                    };
+                }
+                match text.strip_prefix("engine:") {
+                    Some(command) if !command.trim().is_empty() => {
+                        Ok(Strategy::Engine(String::from(command)))
+                    }
+                    _ => Err(format!("Unknown strategy \"{text}\".")),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    // Every strategy plays every other strategy.
+    RoundRobin,
+    // The first strategy plays every other strategy.
+    Gauntlet,
+}
+
+pub struct TournamentOptions {
+    pub format: Format,
+    // Grid sizes in dots.
+    pub sizes: Vec<(i32, i32)>,
+    // Openings played by every pairing on every size, each once with either strategy moving first.
+    pub openings: usize,
+    // Random safe moves made before the strategies take over.
+    pub opening_moves: usize,
+    pub seed: u64,
+    // Thinking time per move for the advisor's playouts, the search and external engines.
+    pub move_time: Duration,
+    // A file that every game is logged to as it is played.
+    pub log: Option<String>,
+    // A port spectators can connect to to watch the games.
+    pub spectators: Option<u16>,
+}
+
+impl TournamentOptions {
+    pub fn new() -> Self {
+        Self {
+            format: Format::RoundRobin,
+            sizes: vec![(4, 4)],
+            openings: 5,
+            opening_moves: 4,
+            seed: 1,
+            move_time: Duration::from_millis(100),
+            log: None,
+            spectators: None,
+        }
+    }
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct GameResult {
+    pub column_count: i32,
+    pub row_count: i32,
+    // Indices of the strategies in the order they moved.
+    pub players: [usize; 2],
+    pub opening: Vec<Wall>,
+    // Every wall set in the game, including the opening.
+    pub moves: Vec<Wall>,
+    pub boxes: [i32; 2],
+    // The player (0 or 1) that lost by failing to make a legal move, and why.
+    pub forfeit: Option<(usize, String)>,
+}
+
+impl GameResult {
+    pub fn margin(&self) -> i32 {
+        // Boxes of the first player minus boxes of the second, with forfeits losing every box.
+        match self.forfeit {
+            Some((0, _)) => -self.box_total(),
+            Some(_) => self.box_total(),
+            None => self.boxes[0] - self.boxes[1],
+        }
+    }
+
+    fn box_total(&self) -> i32 {
+        (self.column_count - 1) * (self.row_count - 1)
+    }
+}
+
+struct Player {
+    strategy: Strategy,
+    engine: Option<ExternalEngine>,
+}
+
+impl Player {
+    fn start(strategy: &Strategy) -> Result<Self, String> {
+        let engine = match strategy {
+            Strategy::Engine(command) => Some(ExternalEngine::start(command)?),
+            _ => None,
+        };
+        Ok(Self {
+            strategy: strategy.clone(),
+            engine,
+        })
+    }
+
+    fn choose(
+        &mut self,
+        game_grid: &Grid,
+        moves: &[Wall],
+        rng: &mut StdRng,
+        move_time: Duration,
+    ) -> Result<Wall, String> {
+        let unset: Vec<Wall> = game_grid.legal_moves().collect();
+        let wall = match &self.strategy {
+            Strategy::Advisor => advisor_move(game_grid, move_time).map(|(wall, _)| wall),
+            Strategy::Heuristics => game_grid
+                .analyze_heuristics()
+                .moves
+                .into_iter()
+                .min_by_key(|wall| (wall.column, wall.row, wall.is_column)),
+            Strategy::Greedy => unset
+                .iter()
+                .find(|wall| completes_square(game_grid, wall))
+                .or_else(|| {
+                    let safe: Vec<&Wall> = unset
+                        .iter()
+                        .filter(|wall| is_safe(game_grid, wall))
+                        .collect();
+                    safe.choose(rng).copied()
+                })
+                .or_else(|| unset.choose(rng))
+                .copied(),
+            Strategy::Random => unset.choose(rng).copied(),
+            Strategy::Search(depth) => {
+                let limits = SearchLimits {
+                    time: move_time,
+                    depth: Some(*depth),
+                };
+                search(game_grid, limits, &CancelToken::new(), |_| {})
+                    .and_then(|best| best.principal_variation.first().copied())
+            }
+            Strategy::Engine(_) => {
+                let engine = self.engine.as_mut().unwrap();
+                Some(engine.best_move(moves, move_time)?.wall)
+            }
+        };
+        match wall {
+            Some(wall) if unset.contains(&Wall { set: false, ..wall }) => Ok(wall),
+            Some(wall) => Err(format!("{} played an illegal move {wall}.", self.strategy)),
+            None => Err(format!("{} did not find a move.", self.strategy)),
+        }
+    }
+}
+
+fn completes_square(game_grid: &Grid, wall: &Wall) -> bool {
+    game_grid.clone().make_move(wall).completed > 0
+}
+
+fn is_safe(game_grid: &Grid, wall: &Wall) -> bool {
+    // A wall is safe if it leaves no square with three walls for the opponent to complete.
+    let mut next_grid = game_grid.clone();
+    next_grid.set_wall_with_wall(wall, true);
+    next_grid
+        .adjacent_squares(wall)
+        .into_iter()
+        .all(|(column, row)| next_grid.get_wall_count(column, row) < 3)
+}
+
+fn random_opening(column_count: i32, row_count: i32, length: usize, rng: &mut StdRng) -> Vec<Wall> {
+    // Random safe moves, stopping early if the safe moves run out.
+    let mut game_grid = Grid::new(column_count, row_count);
+    let mut opening = Vec::new();
+    while opening.len() < length {
+        let safe: Vec<Wall> = game_grid
+            .legal_moves()
+            .filter(|wall| is_safe(&game_grid, wall))
+            .collect();
+        match safe.choose(rng) {
+            Some(wall) => {
+                game_grid.set_wall_with_wall(wall, true);
+                opening.push(*wall);
+            }
+            None => break,
+        }
+    }
+    opening
+}
+
+fn play_game(
+    mut players: [&mut Player; 2],
+    strategies: [usize; 2],
+    size: (i32, i32),
+    opening: &[Wall],
+    rng: &mut StdRng,
+    move_time: Duration,
+    observers: &mut Observers,
+) -> GameResult {
+    let (column_count, row_count) = size;
+    let mut result = GameResult {
+        column_count,
+        row_count,
+        players: strategies,
+        opening: opening.to_vec(),
+        moves: Vec::new(),
+        boxes: [0, 0],
+        forfeit: None,
+    };
+    let mut game = Game::new(column_count, row_count);
+    let names = [
+        players[0].strategy.to_string(),
+        players[1].strategy.to_string(),
+    ];
+    observers.started(&game, [&names[0], &names[1]]);
+    for (index, player) in players.iter_mut().enumerate() {
+        if let Some(engine) = player.engine.as_mut() {
+            if let Err(error) = engine.new_game(column_count, row_count) {
+                result.forfeit = Some((index, error));
+            }
+        }
+    }
+
+    while result.forfeit.is_none() && !game.is_over() {
+        // Opening moves are safe so they never complete a box.
+        let player = game.player();
+        let wall = match opening.get(result.moves.len()) {
+            Some(wall) => Ok(*wall),
+            None => players[player].choose(game.grid(), &result.moves, rng, move_time),
+        };
+        match wall.and_then(|wall| Ok((wall, game.play(player, &wall)?))) {
+            Ok((wall, _)) => {
+                observers.played(&game, player, &wall);
+                result.moves.push(Wall { set: true, ..wall });
+            }
+            Err(error) => result.forfeit = Some((player, error)),
+        }
+    }
+    if let Some((player, _)) = result.forfeit {
+        game.resign(player);
+    }
+    result.boxes = game.boxes();
+    observers.ended(&game);
+    result
+}
+
+pub fn play_tournament(
+    strategies: &[Strategy],
+    options: &TournamentOptions,
+) -> Result<Vec<GameResult>, String> {
+    let mut players = strategies
+        .iter()
+        .map(Player::start)
+        .collect::<Result<Vec<Player>, String>>()?;
+    let pairings: Vec<(usize, usize)> = match options.format {
+        Format::RoundRobin => (0..strategies.len())
+            .flat_map(|first| ((first + 1)..strategies.len()).map(move |second| (first, second)))
+            .collect(),
+        Format::Gauntlet => (1..strategies.len()).map(|second| (0, second)).collect(),
+    };
+
+    let mut observers = Observers::new();
+    if let Some(path) = &options.log {
+        observers.add(Box::new(JsonLog::open(path)?));
+    }
+    if let Some(port) = options.spectators {
+        observers.add(Box::new(Broadcast::listen(port)?));
+    }
+
+    // Every game played from the same seed uses the same openings and random moves.
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut results = Vec::new();
+    for &size in &options.sizes {
+        for _ in 0..options.openings {
+            let opening = random_opening(size.0, size.1, options.opening_moves, &mut rng);
+            for &(first, second) in &pairings {
+                for order in [[first, second], [second, first]] {
+                    let pair = pair_mut(&mut players, order[0], order[1]);
+                    let result = play_game(
+                        pair,
+                        order,
+                        size,
+                        &opening,
+                        &mut rng,
+                        options.move_time,
+                        &mut observers,
+                    );
+                    println!(
+                        "{}x{} {} vs {}: {}",
+                        size.0,
+                        size.1,
+                        strategies[order[0]],
+                        strategies[order[1]],
+                        match &result.forfeit {
+                            Some((player, error)) => format!(
+                                "{} forfeits ({error})",
+                                strategies[result.players[*player]]
+                            ),
+                            None => format!("{}-{}", result.boxes[0], result.boxes[1]),
+                        }
+                    );
+                    results.push(result);
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn pair_mut<T>(items: &mut [T], first: usize, second: usize) -> [&mut T; 2] {
+    // Two different items borrowed mutably in the order given.
+    if first < second {
+        let (low, high) = items.split_at_mut(second);
+        [&mut low[first], &mut high[0]]
+    } else {
+        let (low, high) = items.split_at_mut(first);
+        [&mut high[0], &mut low[second]]
+    }
+}
+
+// Results of one strategy, overall or against one opponent.
+#[derive(Default)]
+pub struct Record {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    // Sum of box margins from the strategy's point of view.
+    pub margin: i32,
+}
+
+impl Record {
+    fn add(&mut self, margin: i32) {
+        match margin {
+            _ if margin > 0 => self.wins += 1,
+            0 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+        self.margin += margin;
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn average_margin(&self) -> f64 {
+        self.margin as f64 / self.games().max(1) as f64
+    }
+
+    pub fn elo(&self) -> (Option<f64>, Option<f64>) {
+        // The Elo difference to the opponents implied by the score, and the half width of its 95%
+        // confidence interval. Neither is finite if every game was won or every game was lost.
+        let games = self.games() as f64;
+        let score = (self.wins as f64 + 0.5 * self.draws as f64) / games;
+        let squares = (self.wins as f64 + 0.25 * self.draws as f64) / games;
+        let deviation = ((squares - score * score).max(0.0) / games).sqrt();
+        // Keep the interval inside the scores that have a finite Elo difference.
+        let limit = 0.5 / games;
+        let difference = elo_difference(score);
+        let error = match (
+            elo_difference((score - 1.96 * deviation).max(limit)),
+            elo_difference((score + 1.96 * deviation).min(1.0 - limit)),
+        ) {
+            (Some(low), Some(high)) if difference.is_some() => Some((high - low) / 2.0),
+            _ => None,
+        };
+        (difference, error)
+    }
+}
+
+fn elo_difference(score: f64) -> Option<f64> {
+    if score > 0.0 && score < 1.0 {
+        // Adding zero turns -0 (an even score) into 0.
+        Some(-400.0 * (1.0 / score - 1.0).log10() + 0.0)
+    } else {
+        None
+    }
+}
+
+pub fn standings(strategy_count: usize, results: &[GameResult]) -> Vec<Record> {
+    let mut records: Vec<Record> = (0..strategy_count).map(|_| Record::default()).collect();
+    for result in results {
+        records[result.players[0]].add(result.margin());
+        records[result.players[1]].add(-result.margin());
+    }
+    records
+}
+
+pub fn pairing_records(strategy_count: usize, results: &[GameResult]) -> Vec<Vec<Record>> {
+    // Records indexed by [strategy][opponent].
+    let mut records: Vec<Vec<Record>> = (0..strategy_count)
+        .map(|_| (0..strategy_count).map(|_| Record::default()).collect())
+        .collect();
+    for result in results {
+        let [first, second] = result.players;
+        records[first][second].add(result.margin());
+        records[second][first].add(-result.margin());
+    }
+    records
+}
+
+fn format_elo(record: &Record) -> String {
+    match record.elo() {
+        (Some(difference), Some(error)) => format!("{difference:+.0} ± {error:.0}"),
+        (Some(difference), None) => format!("{difference:+.0}"),
+        (None, _) if record.wins > 0 => String::from("+inf"),
+        (None, _) => String::from("-inf"),
+    }
+}
+
+pub fn report(strategies: &[Strategy], results: &[GameResult]) -> String {
+    let mut text = String::new();
+    let width = strategies
+        .iter()
+        .map(|strategy| strategy.to_string().chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let mut table = |title: &str, rows: Vec<(String, &Record)>| {
+        let _ = writeln!(text, "{title}");
+        let _ = writeln!(
+            text,
+            "{:<width$} {:>5} {:>5} {:>5} {:>6} {:>7} {:>12}",
+            "Strategy", "Games", "Wins", "Draws", "Losses", "Margin", "Elo"
+        );
+        for (name, record) in rows.into_iter().filter(|(_, record)| record.games() > 0) {
+            let _ = writeln!(
+                text,
+                "{name:<width$} {:>5} {:>5} {:>5} {:>6} {:>+7.2} {:>12}",
+                record.games(),
+                record.wins,
+                record.draws,
+                record.losses,
+                record.average_margin(),
+                format_elo(record)
+            );
+        }
+        let _ = writeln!(text);
+    };
+
+    let standings = standings(strategies.len(), results);
+    table(
+        "Standings",
+        strategies
+            .iter()
+            .map(Strategy::to_string)
+            .zip(standings.iter())
+            .collect(),
+    );
+    let pairings = pairing_records(strategies.len(), results);
+    for (strategy, records) in strategies.iter().zip(pairings.iter()) {
+        table(
+            &format!("{strategy} against"),
+            strategies
+                .iter()
+                .map(Strategy::to_string)
+                .zip(records.iter())
+                .collect(),
+        );
+    }
+    text
+}
+
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{value:.1}"),
+        None => String::from("null"),
+    }
+}
+
+fn json_record(record: &Record) -> String {
+    let (elo, elo_error) = record.elo();
+    format!(
+        "\"games\": {}, \"wins\": {}, \"draws\": {}, \"losses\": {}, \"average_margin\": {:.3}, \
+\"elo\": {}, \"elo_error\": {}",
+        record.games(),
+        record.wins,
+        record.draws,
+        record.losses,
+        record.average_margin(),
+        json_number(elo),
+        json_number(elo_error)
+    )
+}
+
+pub fn to_json(
+    strategies: &[Strategy],
+    options: &TournamentOptions,
+    results: &[GameResult],
+) -> String {
+    let names: Vec<String> = strategies
+        .iter()
+        .map(|strategy| quote(&strategy.to_string()))
+        .collect();
+    let walls = |walls: &[Wall]| {
+        walls
+            .iter()
+            .map(|wall| format!("\"{wall}\""))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    let mut json = String::from("{\n");
+    let _ = writeln!(json, "  \"seed\": {},", options.seed);
+    let _ = writeln!(
+        json,
+        "  \"format\": \"{}\",",
+        match options.format {
+            Format::RoundRobin => "round-robin",
+            Format::Gauntlet => "gauntlet",
+        }
+    );
+    let _ = writeln!(json, "  \"strategies\": [{}],", names.join(", "));
+    let _ = writeln!(json, "  \"games\": [");
+    for (index, result) in results.iter().enumerate() {
+        let _ = writeln!(
+            json,
+            "    {{\"size\": \"{}x{}\", \"first\": {}, \"second\": {}, \"opening\": [{}], \
+\"moves\": [{}], \"boxes\": [{}, {}], \"margin\": {}, \"forfeit\": {}}}{}",
+            result.column_count,
+            result.row_count,
+            names[result.players[0]],
+            names[result.players[1]],
+            walls(&result.opening),
+            walls(&result.moves),
+            result.boxes[0],
+            result.boxes[1],
+            result.margin(),
+            match &result.forfeit {
+                Some((player, error)) => format!(
+                    "{{\"player\": {}, \"reason\": {}}}",
+                    names[result.players[*player]],
+                    quote(error)
+                ),
+                None => String::from("null"),
+            },
+            if index + 1 < results.len() { "," } else { "" }
+        );
+    }
+    let _ = writeln!(json, "  ],");
+    let _ = writeln!(json, "  \"standings\": [");
+    let standings = standings(strategies.len(), results);
+    let pairings = pairing_records(strategies.len(), results);
+    for (index, record) in standings.iter().enumerate() {
+        let opponents: Vec<String> = pairings[index]
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.games() > 0)
+            .map(|(opponent, record)| {
+                format!(
+                    "{{\"opponent\": {}, {}}}",
+                    names[opponent],
+                    json_record(record)
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            json,
+            "    {{\"strategy\": {}, {}, \"opponents\": [{}]}}{}",
+            names[index],
+            json_record(record),
+            opponents.join(", "),
+            if index + 1 < standings.len() { "," } else { "" }
+        );
+    }
+    let _ = writeln!(json, "  ]");
+    json.push_str("}\n");
+    json
+}
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    let mut options = TournamentOptions::new();
+    let mut strategies = Vec::new();
+    let mut output = None;
+
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = || {
+            arguments
+                .next()
+                .ok_or_else(|| format!("Missing value for {argument}.\n{TOURNAMENT_USAGE}"))
+        };
+        let invalid = |value: &str| format!("Invalid {argument} \"{value}\".\n{TOURNAMENT_USAGE}");
+        match argument.as_str() {
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "round-robin" => Format::RoundRobin,
+                    "gauntlet" => Format::Gauntlet,
+                    format => return Err(invalid(format)),
+                }
+            }
+            "--sizes" => {
+                options.sizes = value()?
+                    .split(',')
+                    .map(parse_dots)
+                    .collect::<Result<Vec<(i32, i32)>, String>>()
+                    .map_err(|error| format!("{error}\n{TOURNAMENT_USAGE}"))?
+            }
+            "--openings" => {
+                let count = value()?;
+                options.openings = count.parse().map_err(|_| invalid(count))?;
+            }
+            "--opening-moves" => {
+                let count = value()?;
+                options.opening_moves = count.parse().map_err(|_| invalid(count))?;
+            }
+            "--seed" => {
+                let seed = value()?;
+                options.seed = seed.parse().map_err(|_| invalid(seed))?;
+            }
+            "--move-time" => {
+                let time = value()?;
+                options.move_time = Duration::from_millis(time.parse().map_err(|_| invalid(time))?);
+            }
+            "--output" => output = Some(value()?),
+            "--log" => options.log = Some(value()?.clone()),
+            "--spectators" => {
+                let port = value()?;
+                options.spectators = Some(port.parse().map_err(|_| invalid(port))?);
+            }
+            _ if !argument.starts_with("--") => strategies.push(
+                argument
+                    .parse::<Strategy>()
+                    .map_err(|error| format!("{error}\n{TOURNAMENT_USAGE}"))?,
+            ),
+            _ => {
+                return Err(format!(
+                    "Unexpected argument \"{argument}\".\n{TOURNAMENT_USAGE}"
+                ))
+            }
+        }
+    }
+    if strategies.len() < 2 {
+        return Err(format!(
+            "At least two strategies are needed.\n{TOURNAMENT_USAGE}"
+        ));
+    }
+
+    let results = play_tournament(&strategies, &options)?;
+    println!();
+    print!("{}", report(&strategies, &results));
+    if let Some(path) = output {
+        fs::write(path, to_json(&strategies, &options, &results))
+            .map_err(|error| format!("Unable to write {path}: {error}."))?;
+        println!("Results written to {path}.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_record_has_no_elo_difference() {
+        let record = Record {
+            wins: 3,
+            draws: 2,
+            losses: 3,
+            margin: 0,
+        };
+        let (difference, error) = record.elo();
+        assert_eq!(difference, Some(0.0));
+        assert!(error.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn perfect_record_has_no_finite_elo() {
+        let record = Record {
+            wins: 4,
+            draws: 0,
+            losses: 0,
+            margin: 12,
+        };
+        assert_eq!(record.elo(), (None, None));
+    }
+
+    #[test]
+    fn parses_search_strategies() {
+        let strategy: Strategy = "search:4".parse().unwrap();
+        assert!(strategy == Strategy::Search(4));
+        assert_eq!(strategy.to_string(), "search:4");
+        assert!("search:0".parse::<Strategy>().is_err());
+        assert!("search:deep".parse::<Strategy>().is_err());
+    }
+
+    #[test]
+    fn tournaments_are_repeatable() {
+        let strategies = [Strategy::Advisor, Strategy::Greedy, Strategy::Random];
+        let mut options = TournamentOptions::new();
+        options.sizes = vec![(3, 3), (4, 3)];
+        options.openings = 2;
+        // Time for every playout, so the advisor plays the same moves however fast the machine is.
+        options.move_time = Duration::from_secs(60);
+        let margins =
+            |results: Vec<GameResult>| results.iter().map(GameResult::margin).collect::<Vec<i32>>();
+        let first = play_tournament(&strategies, &options).unwrap();
+        assert_eq!(first.len(), 2 * 2 * 3 * 2);
+        for result in &first {
+            assert!(result.forfeit.is_none());
+            assert_eq!(
+                result.boxes[0] + result.boxes[1],
+                (result.column_count - 1) * (result.row_count - 1)
+            );
+        }
+        assert_eq!(
+            margins(first),
+            margins(play_tournament(&strategies, &options).unwrap())
+        );
+    }
+
+    #[test]
+    fn the_advisor_does_not_lose_to_its_heuristics() {
+        // The books, tablebases and long chain parity narrowing the safe moves should not play
+        // worse than the first of the heuristics' moves.
+        let strategies = [Strategy::Advisor, Strategy::Heuristics];
+        let mut options = TournamentOptions::new();
+        options.sizes = vec![(5, 4)];
+        options.opening_moves = 2;
+        options.move_time = Duration::from_secs(60);
+        let results = play_tournament(&strategies, &options).unwrap();
+        let advisor = &standings(2, &results)[0];
+        assert_eq!(advisor.games(), 10);
+        assert!(advisor.wins > advisor.losses);
+        assert!(advisor.margin > 0);
+    }
+}
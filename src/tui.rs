@@ -0,0 +1,356 @@
+use crate::cursor::Cursor;
+use crate::engine::parse_dots;
+use crate::grid::{Analysis, Grid, Tier};
+use crate::history::{MoveHistory, Owners};
+use crate::wall::{column_label, row_label, Wall};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+// The analysis board drawn in a terminal, for when no window can be opened. Dots are three
+// characters apart with the squares between them filled by wall count, or by owner once completed,
+// using the colors of the light theme.
+
+const TUI_USAGE: &str = "Usage: dots_and_boxes tui [--dots COLUMNSxROWS]";
+const HELP: &str = "Arrows move, Tab turns, Enter sets or clears, U undoes, A toggles analysis, \
+Q quits.";
+
+// Characters between neighbouring dots, and the width of the row labels left of the grid.
+const SQUARE_WIDTH: usize = 3;
+const LABEL_WIDTH: usize = 3;
+
+const SQUARE_FILL: [Option<Color>; 5] = [
+    None,
+    None,
+    Some(Color::DarkYellow),
+    Some(Color::DarkBlue),
+    Some(Color::Black),
+];
+const PLAYER_FILL: [Color; 2] = [Color::DarkRed, Color::DarkCyan];
+const PLAYER_LETTERS: [char; 2] = ['A', 'B'];
+const OPTIMAL_MOVE_STROKE: Color = Color::Green;
+const CURSOR_STROKE: Color = Color::Red;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    character: char,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+impl Cell {
+    fn plain(character: char) -> Self {
+        Self {
+            character,
+            foreground: None,
+            background: None,
+        }
+    }
+}
+
+fn wall_cell(wall: &Wall, optimal_moves: &HashSet<Wall>, cursor: &Cursor) -> Cell {
+    // Set walls are solid lines and highlighted walls that are not set are dashed.
+    let is_cursor = cursor.is_column == wall.is_column
+        && cursor.column == wall.column
+        && cursor.row == wall.row;
+    let foreground = if is_cursor {
+        Some(CURSOR_STROKE)
+    } else if optimal_moves.contains(wall) {
+        Some(OPTIMAL_MOVE_STROKE)
+    } else {
+        None
+    };
+    let character = match (wall.set, foreground.is_some(), wall.is_column) {
+        (true, _, true) => '│',
+        (true, _, false) => '─',
+        (false, true, true) => '┆',
+        (false, true, false) => '┄',
+        (false, false, _) => ' ',
+    };
+    Cell {
+        character,
+        foreground,
+        background: None,
+    }
+}
+
+fn render_board(
+    game_grid: &Grid,
+    owners: &Owners,
+    optimal_moves: &HashSet<Wall>,
+    cursor: &Cursor,
+) -> Vec<Vec<Cell>> {
+    // The board as lines of cells: the column letters, then alternating lines of dots with row
+    // walls and of column walls with squares.
+    let width = LABEL_WIDTH + (game_grid.column_count() as usize - 1) * (SQUARE_WIDTH + 1) + 1;
+    let mut lines = Vec::new();
+
+    let mut header = vec![Cell::plain(' '); width];
+    for column in 0..game_grid.column_count() {
+        header[LABEL_WIDTH + column as usize * (SQUARE_WIDTH + 1)] =
+            Cell::plain(column_label(column));
+    }
+    lines.push(header);
+
+    for row in 0..game_grid.row_count() {
+        let mut dots = vec![Cell::plain(' '); width];
+        for (index, character) in format!("{:>2}", row_label(row)).chars().enumerate() {
+            dots[index] = Cell::plain(character);
+        }
+        for column in 0..game_grid.column_count() {
+            let x = LABEL_WIDTH + column as usize * (SQUARE_WIDTH + 1);
+            dots[x] = Cell::plain('•');
+            if column < game_grid.column_count() - 1 {
+                // Row walls do not exist in the last column.
+                let cell = wall_cell(
+                    &game_grid.get_wall(false, column, row),
+                    optimal_moves,
+                    cursor,
+                );
+                dots[x + 1..x + 1 + SQUARE_WIDTH].fill(cell);
+            }
+        }
+        lines.push(dots);
+
+        if row == game_grid.row_count() - 1 {
+            // Column walls and squares do not exist below the last row.
+            break;
+        }
+        let mut squares = vec![Cell::plain(' '); width];
+        for column in 0..game_grid.column_count() {
+            let x = LABEL_WIDTH + column as usize * (SQUARE_WIDTH + 1);
+            squares[x] = wall_cell(
+                &game_grid.get_wall(true, column, row),
+                optimal_moves,
+                cursor,
+            );
+            if column < game_grid.column_count() - 1 {
+                let owner = owners[column as usize][row as usize];
+                let background = match owner {
+                    Some(player) => Some(PLAYER_FILL[player]),
+                    None => SQUARE_FILL[game_grid.get_wall_count(column, row) as usize],
+                };
+                for cell in &mut squares[x + 1..x + 1 + SQUARE_WIDTH] {
+                    cell.background = background;
+                }
+                if let Some(player) = owner {
+                    squares[x + 1 + SQUARE_WIDTH / 2].character = PLAYER_LETTERS[player];
+                }
+            }
+        }
+        lines.push(squares);
+    }
+    lines
+}
+
+fn status(
+    game_grid: &Grid,
+    history: &MoveHistory,
+    cursor: &Cursor,
+    analysis: Option<&Analysis>,
+) -> String {
+    let owners = history.get_owners(game_grid.column_count(), game_grid.row_count());
+    let boxes = |player: usize| {
+        owners
+            .iter()
+            .flatten()
+            .filter(|&&owner| owner == Some(player))
+            .count()
+    };
+    let cursor_wall = Wall {
+        set: false,
+        is_column: cursor.is_column,
+        column: cursor.column,
+        row: cursor.row,
+    };
+    let player = history.next_player(game_grid.column_count(), game_grid.row_count());
+    let analysis = if let Some(analysis) = analysis {
+        let tier = match analysis.tier {
+            Some(Tier::Capture) => "capture",
+            Some(Tier::Safe) => "safe moves",
+            Some(Tier::Sacrifice) => "sacrifice",
+            None => "no moves",
+        };
+        let countdown = game_grid.countdown();
+        let opener = if countdown.mover_opens {
+            player
+        } else {
+            1 - player
+        };
+        format!(
+            "{tier}, {} safe moves left, {} opens the first chain",
+            countdown.safe_moves, PLAYER_LETTERS[opener]
+        )
+    } else {
+        String::from("off")
+    };
+    format!(
+        "Player {} to move. Boxes: A {}, B {}. Cursor: {cursor_wall}. Analysis: {analysis}.",
+        PLAYER_LETTERS[player],
+        boxes(0),
+        boxes(1)
+    )
+}
+
+fn draw(
+    output: &mut impl Write,
+    game_grid: &Grid,
+    history: &MoveHistory,
+    cursor: &Cursor,
+    show_analysis: bool,
+) -> io::Result<()> {
+    // The position is analysed once for both the highlights and the status line.
+    let owners = history.get_owners(game_grid.column_count(), game_grid.row_count());
+    let analysis = show_analysis.then(|| game_grid.analyze());
+    let no_moves = HashSet::new();
+    let optimal_moves = analysis
+        .as_ref()
+        .map_or(&no_moves, |analysis| &analysis.moves);
+    queue!(output, Clear(ClearType::All))?;
+    let lines = render_board(game_grid, &owners, optimal_moves, cursor);
+    for (y, line) in lines.iter().enumerate() {
+        queue!(output, MoveTo(0, y as u16))?;
+        for cell in line {
+            queue!(output, ResetColor)?;
+            if let Some(color) = cell.foreground {
+                queue!(output, SetForegroundColor(color))?;
+            }
+            if let Some(color) = cell.background {
+                queue!(output, SetBackgroundColor(color))?;
+            }
+            queue!(output, Print(cell.character))?;
+        }
+    }
+    queue!(
+        output,
+        ResetColor,
+        MoveTo(0, lines.len() as u16 + 1),
+        Print(status(game_grid, history, cursor, analysis.as_ref())),
+        MoveTo(0, lines.len() as u16 + 2),
+        Print(HELP)
+    )?;
+    output.flush()
+}
+
+// Restores the terminal when dropped, including when the loop ends with an error.
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn play(game_grid: &mut Grid) -> io::Result<()> {
+    let mut history = MoveHistory::new();
+    let mut cursor = Cursor::new();
+    let mut show_analysis = true;
+    let _screen = Screen::enter()?;
+    let mut output = io::stdout();
+    loop {
+        draw(&mut output, game_grid, &history, &cursor, show_analysis)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Up => cursor.move_by(game_grid, 0, -1),
+            KeyCode::Down => cursor.move_by(game_grid, 0, 1),
+            KeyCode::Left => cursor.move_by(game_grid, -1, 0),
+            KeyCode::Right => cursor.move_by(game_grid, 1, 0),
+            KeyCode::Tab => cursor.toggle_orientation(game_grid),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let wall = game_grid.get_wall(cursor.is_column, cursor.column, cursor.row);
+                history.toggle(game_grid, &wall);
+            }
+            KeyCode::Char('u') => {
+                if let Some(wall) = history.undo(game_grid) {
+                    cursor.move_to(game_grid, wall.is_column, wall.column, wall.row);
+                }
+            }
+            KeyCode::Char('a') => show_analysis = !show_analysis,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+pub fn run(arguments: &[String]) -> Result<(), String> {
+    let (column_count, row_count) = match arguments {
+        [] => (8, 10),
+        [flag, dots] if flag == "--dots" => {
+            parse_dots(dots).map_err(|error| format!("{error}\n{TUI_USAGE}"))?
+        }
+        _ => return Err(String::from(TUI_USAGE)),
+    };
+    let mut game_grid = Grid::new(column_count, row_count);
+    play(&mut game_grid).map_err(|error| format!("Terminal error: {error}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Vec<Cell>]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|cell| cell.character).collect())
+            .collect()
+    }
+
+    #[test]
+    fn renders_walls_owners_and_highlights() {
+        let mut game_grid = Grid::new(3, 2);
+        let mut history = MoveHistory::new();
+        for notation in ["a1h", "a1v", "c1v", "a2h", "b1v"] {
+            history.toggle(&mut game_grid, &notation.parse().unwrap());
+        }
+        let owners = history.get_owners(3, 2);
+        let cursor = Cursor::new();
+        let lines = render_board(&game_grid, &owners, &game_grid.get_optimal_moves(), &cursor);
+        assert_eq!(
+            text(&lines),
+            [
+                "   a   b   c",
+                " 1 •───•┄┄┄•",
+                "   │ A │   │",
+                " 2 •───•┄┄┄•"
+            ]
+        );
+        // The completed square is filled with its owner's color and the cursor is on a1h.
+        assert_eq!(lines[2][5].background, Some(PLAYER_FILL[0]));
+        assert_eq!(lines[1][4].foreground, Some(CURSOR_STROKE));
+        assert_eq!(lines[1][8].foreground, Some(OPTIMAL_MOVE_STROKE));
+        assert_eq!(lines[2][9].background, SQUARE_FILL[2]);
+    }
+
+    #[test]
+    fn reports_the_analysis_in_the_status_line() {
+        let game_grid = Grid::new(3, 2);
+        let history = MoveHistory::new();
+        let cursor = Cursor::new();
+        let analysis = game_grid.analyze();
+        assert_eq!(
+            status(&game_grid, &history, &cursor, Some(&analysis)),
+            "Player A to move. Boxes: A 0, B 0. Cursor: a1h. Analysis: safe moves, 4 safe moves \
+left, A opens the first chain."
+        );
+        assert!(status(&game_grid, &history, &cursor, None).ends_with("Analysis: off."));
+    }
+}
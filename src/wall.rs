@@ -1,7 +1,71 @@
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Wall {
     pub set: bool,
     pub is_column: bool,
     pub column: i32,
     pub row: i32,
 }
+
+// Walls are written as the dot they start from followed by their direction: the dot's column
+// letter (a is the leftmost column), its row number (1 is the top row) and "v" for a column wall
+// going down or "h" for a row wall going right. For example "c2h" joins dots c2 and d2.
+
+pub fn column_label(column: i32) -> char {
+    // Grids have at most 26 columns (see Grid::new), so only walls outside every grid are left
+    // without a letter.
+    match u8::try_from(column) {
+        Ok(column) if column < 26 => (b'a' + column) as char,
+        _ => '?',
+    }
+}
+
+pub fn row_label(row: i32) -> String {
+    (row + 1).to_string()
+}
+
+impl fmt::Display for Wall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            column_label(self.column),
+            row_label(self.row),
+            if self.is_column { 'v' } else { 'h' }
+        )
+    }
+}
+
+impl FromStr for Wall {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut characters = text.chars();
+        let column = match characters.next() {
+            Some(letter @ 'a'..='z') => letter as i32 - 'a' as i32,
+            _ => {
+                return Err(format!(
+                    "Wall \"{text}\" does not start with a column letter."
+                ))
+            }
+        };
+        let is_column = match characters.next_back() {
+            Some('v') => true,
+            Some('h') => false,
+            _ => return Err(format!("Wall \"{text}\" does not end with \"v\" or \"h\".")),
+        };
+        let row = match characters.as_str().parse::<i32>() {
+            Ok(number) if number >= 1 => number - 1,
+            _ => return Err(format!("Wall \"{text}\" does not have a valid row number.")),
+        };
+        Ok(Wall {
+            set: false,
+            is_column,
+            column,
+            row,
+        })
+    }
+}